@@ -0,0 +1,42 @@
+// How long the assembler takes on a large program, run with `cargo bench`.
+#![feature(test)]
+
+extern crate test;
+
+use std::fmt::Write;
+
+use mips_int::assembler::Assembler;
+use test::Bencher;
+
+/// A program with `blocks` copies of a loop and a string, a few thousand lines for 500 blocks
+fn large_program(blocks: usize) -> String {
+	let mut source = String::from("COUNT = 10\n.data\n");
+	for i in 0..blocks {
+		writeln!(source, "msg_{}:\t.asciiz \"block {}, with a comma\"\t# a comment", i, i).unwrap();
+		writeln!(source, "\t.align 2\ntable_{}:\t.word 1, 2, 3, msg_{}, 0:4", i, i).unwrap();
+	}
+	source.push_str(".text\nmain:\n");
+	for i in 0..blocks {
+		writeln!(source, "loop_{}:", i).unwrap();
+		writeln!(source, "\taddi $t0, $t0, -(COUNT << 1)\t# count down").unwrap();
+		writeln!(source, "\tlw $t1, 8($sp)").unwrap();
+		writeln!(source, "\tlw $t2, table_{}($t1)", i).unwrap();
+		writeln!(source, "\tli $t3, 0x12345678").unwrap();
+		writeln!(source, "\tla $a0, msg_{}", i).unwrap();
+		writeln!(source, "\tsll $t4, $t3, 2").unwrap();
+		writeln!(source, "\tbne $t0, $zero, loop_{}", i).unwrap();
+	}
+	source.push_str("\tj main\n");
+	source
+}
+
+#[bench]
+fn assemble_large_program(b: &mut Bencher) {
+	let source = large_program(500);
+	b.iter(|| Assembler::new().assemble(&source).unwrap());
+}
+
+#[bench]
+fn assemble_game(b: &mut Bencher) {
+	b.iter(|| Assembler::new().assemble_file("data/game.asm").unwrap());
+}
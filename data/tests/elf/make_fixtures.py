@@ -0,0 +1,99 @@
+#!/usr/bin/env python3
+# Writes the statically linked ELF executables the loader tests run against,
+# laid out the way a MIPS cross linker would lay them out:
+# the first PT_LOAD starts with the headers, the code follows at 0x00400100,
+# and the data and .bss sit in a second PT_LOAD at 0x00410200.
+#
+# The object files next to them come straight from llvm-mc:
+#	llvm-mc -triple=mips -filetype=obj reloc.s -o reloc_mips.o
+#	llvm-mc -triple=i386 -filetype=obj x86.s -o x86.o
+import struct
+
+TEXT_ADDR = 0x00400100
+DATA_ADDR = 0x00410200
+CODE = [
+	0x3c040041,	# lui $a0, 0x41
+	0x34840200,	# ori $a0, $a0, 0x200		msg
+	0x24020004,	# addiu $v0, $zero, 4		print string
+	0x0000000c,	# syscall
+	0x2402000a,	# addiu $v0, $zero, 10		exit
+	0x0000000c,	# syscall
+]
+DATA = b"hello\n\0\0"
+BSS = 8
+
+
+def build(endian, relocations=False):
+	e = '>' if endian == 'big' else '<'
+	code = b''.join(struct.pack(e + 'I', w) for w in CODE)
+	text_off, data_off = 0x100, 0x200
+
+	shstrtab = b'\0'
+	def name(n):
+		nonlocal shstrtab
+		idx = len(shstrtab)
+		shstrtab += n.encode() + b'\0'
+		return idx
+	strtab = b'\0'
+	def sym_name(n):
+		nonlocal strtab
+		idx = len(strtab)
+		strtab += n.encode() + b'\0'
+		return idx
+
+	# null, a section and a file symbol that aren't labels,
+	# then the locals, then the globals and one left undefined
+	symbols = [
+		(0, 0, 0, 0, 0),
+		(0, TEXT_ADDR, 0, 0x03, 1),						# STT_SECTION .text
+		(sym_name("start.s"), 0, 0, 0x04, 0xfff1),		# STT_FILE
+		(sym_name("msg"), DATA_ADDR, 7, 0x01, 2),		# local object
+		(sym_name("counter"), DATA_ADDR + 8, 8, 0x01, 3),
+		(sym_name("_start"), TEXT_ADDR, 24, 0x12, 1),	# global function
+		(sym_name("_gp"), 0x00418200, 0, 0x10, 0xfff1),	# global absolute
+		(sym_name("missing"), 0, 0, 0x10, 0),			# undefined
+	]
+	symtab = b''.join(struct.pack(e + 'IIIBBH', n, v, s, i, 0, x) for n, v, s, i, x in symbols)
+	rel = struct.pack(e + 'II', TEXT_ADDR, 5 << 8 | 4) if relocations else b''
+
+	out = bytearray(0x200)
+	out[text_off:text_off + len(code)] = code
+	out += DATA
+	sections = [(0, 0, 0, 0, 0, 0, 0, 0, 0, 0)]
+	sections.append((name(".text"), 1, 6, TEXT_ADDR, text_off, len(code), 0, 0, 4, 0))
+	sections.append((name(".data"), 1, 3, DATA_ADDR, data_off, len(DATA), 0, 0, 4, 0))
+	sections.append((name(".bss"), 8, 3, DATA_ADDR + len(DATA), len(out), BSS, 0, 0, 4, 0))
+	symtab_idx = len(sections)
+	sections.append((name(".symtab"), 2, 0, 0, len(out), len(symtab), symtab_idx + 1, 5, 4, 16))
+	out += symtab
+	sections.append((name(".strtab"), 3, 0, 0, len(out), len(strtab), 0, 0, 1, 0))
+	out += strtab
+	if relocations:
+		sections.append((name(".rel.text"), 9, 0, 0, len(out), len(rel), symtab_idx, 1, 4, 8))
+		out += rel
+	shstrndx = len(sections)
+	sections.append((name(".shstrtab"), 3, 0, 0, 0, 0, 0, 0, 1, 0))
+	sections[-1] = sections[-1][:4] + (len(out), len(shstrtab)) + sections[-1][6:]
+	out += shstrtab
+	while len(out) % 4:
+		out += b'\0'
+	shoff = len(out)
+	for sh in sections:
+		out += struct.pack(e + 'IIIIIIIIII', *sh)
+
+	ident = b'\x7fELF' + bytes([1, 2 if endian == 'big' else 1, 1]) + bytes(9)
+	header = ident + struct.pack(e + 'HHIIIIIHHHHHH', 2, 8, 1, TEXT_ADDR, 52, shoff,
+								 0x50001000, 52, 32, 2, 40, len(sections), shstrndx)
+	phdrs = struct.pack(e + 'IIIIIIII', 1, 0, 0x00400000, 0x00400000, text_off + len(code),
+						text_off + len(code), 5, 0x10000)
+	phdrs += struct.pack(e + 'IIIIIIII', 1, data_off, DATA_ADDR, DATA_ADDR, len(DATA),
+						 len(DATA) + BSS, 6, 0x10000)
+	out[:len(header) + len(phdrs)] = header + phdrs
+	return bytes(out)
+
+
+for file, endian, relocations in [("hello_be.elf", 'big', False),
+								  ("hello_le.elf", 'little', False),
+								  ("relocations.elf", 'big', True)]:
+	with open(file, 'wb') as f:
+		f.write(build(endian, relocations))
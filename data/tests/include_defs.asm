@@ -0,0 +1,3 @@
+# shared definitions
+.eqv WORD 4
+PRINT_INT = 1
@@ -0,0 +1,4 @@
+.data
+.text
+main:
+.include "include_defs.asm"	# constants can't go in the text segment
@@ -0,0 +1,8 @@
+# pulls its constants in from a shared file
+.include "include_defs.asm"
+
+.data
+.text
+main:
+	li	$v0, PRINT_INT
+	addi	$sp, $sp, -WORD
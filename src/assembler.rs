@@ -0,0 +1,1203 @@
+// The assembler turns MIPS source into a `Program`: the bytes of every segment,
+// the labels, a source map and a listing, and the warnings it found on the way.
+// It doesn't need an interpreter, and one program can be loaded into as many
+// interpreters as wanted, or into the same one again to run it from the start.
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use crate::{expression, instruction, pseudo, parser};
+use crate::lexer::parse_number;
+use crate::parser::{Expression, Operand, OperandKind, Statement};
+use crate::preprocessor::{self, SourceLine};
+use crate::listing::{Listing, ListingEntry, Segment};
+use crate::source_map::{SourceLocation, SourceMap};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::memory::{self, Memory};
+use crate::image::Image;
+use crate::instruction::{DecodedInstruction, OPName};
+use crate::mips_int::MipsError;
+
+/// An assembled program, ready to be loaded into an interpreter
+#[derive(Clone)]
+pub struct Program {
+	pub image: Image,					// segments, labels, and `main` as the entry point
+	pub source_map: SourceMap,
+	pub diagnostics: Vec<Diagnostic>,	// only warnings, errors stop a program from being made
+	pub(crate) listing: Listing,
+}
+
+impl Program {
+	/// A listing of the program: every line's address, machine code and source,
+	/// followed by the symbol table and a cross reference of labels.
+	pub fn listing(&self) -> String {
+		let mut memory = Memory::new();
+		for segment in self.image.segments.iter() {
+			let _ = memory.map(segment.clone());
+		}
+		self.listing.render(&memory, &self.image.symbols)
+	}
+}
+
+enum LoadingState {
+	FileOpen,
+	Data,
+	Code
+}
+
+/// The assembler walks the source twice. The first pass only sizes every line
+/// so that all labels are known, the second pass does the actual encoding.
+#[derive(Copy, Clone, PartialEq)]
+enum AssemblerPass {
+	Symbols,
+	Encode,
+}
+
+/// Everything that changes while walking the lines of one pass
+struct PassState {
+	state: LoadingState,
+	variables: HashMap<String, i32>,
+	// keeps track of every byte, not every line
+	data_pointer: u32,				// address of the next byte of data
+	text_pointer: u32,				// address of the next instruction
+	pending_labels: Vec<String>,	// labels with nothing placed after them yet
+	line_start: u32,				// where the current line's bytes start, after any padding
+	instructions: Vec<String>,		// real instructions the current line became
+	last_label: Option<String>,		// closest label above the current line
+	defined: HashSet<String>,		// labels defined so far, to catch duplicates
+}
+
+impl PassState {
+	/// Where the next thing goes in the segment being assembled
+	fn address(&self) -> u32 {
+		match self.state {
+			LoadingState::Code => { self.text_pointer }
+			_ => { self.data_pointer }
+		}
+	}
+}
+
+/// How the hardware widens a 16 bit immediate back out to 32 bits
+#[derive(Copy, Clone)]
+enum ImmediateKind {
+	SignExtended,	// addi, addiu, slti, sltiu
+	ZeroExtended,	// andi, ori, xori, lui
+}
+
+/// Everything an instruction encoder needs to know about where it lives.
+#[derive(Copy, Clone)]
+pub(crate) struct LineContext<'a> {
+	pub(crate) labels: &'a HashMap<String, u32>,
+	pub(crate) variables: &'a HashMap<String, i32>,
+	pub(crate) address: u32,		// byte address of the instruction being encoded
+	pub(crate) line_number: usize,	// 1 based line in the source file
+	pass: AssemblerPass,
+}
+
+/// The operands of an instruction, taken one at a time by its encoder
+type Operands<'a, 's> = std::slice::Iter<'a, Operand<'s>>;
+
+fn next_register(terms: &mut Operands) -> Option<usize> {
+	match terms.next()?.kind {
+		OperandKind::Register(idx) => { Some(idx) }
+		_ => { None }
+	}
+}
+
+/// Reads the `$rd, $rs, $rt` operands of an R-type instruction
+fn load_3_terms(op: u32, terms: &mut Operands) -> Option<DecodedInstruction> {
+	let rd = next_register(terms)?;
+	let rs = next_register(terms)?;
+	let rt = next_register(terms)?;
+	Some(DecodedInstruction::r(op, rs, rt, rd, 0))
+}
+
+/// Reads the `$rs, $rt` operands of `mult` and `div`
+fn load_2_terms(op: u32, terms: &mut Operands) -> Option<DecodedInstruction> {
+	let rs = next_register(terms)?;
+	let rt = next_register(terms)?;
+	Some(DecodedInstruction::r(op, rs, rt, 0, 0))
+}
+
+/// Reads the `$rd, $rt, $rs` operands of the variable shifts.
+/// Note the value being shifted comes before the shift amount.
+fn load_shift_variable_terms(op: u32, terms: &mut Operands) -> Option<DecodedInstruction> {
+	let rd = next_register(terms)?;
+	let rt = next_register(terms)?;
+	let rs = next_register(terms)?;
+	Some(DecodedInstruction::r(op, rs, rt, rd, 0))
+}
+
+/// Finds the byte address of a label.
+/// During the first pass labels further down the file are not known yet,
+/// so the current address is handed back as a placeholder.
+/// A plain number is taken as the address itself, which is how
+/// the disassembler writes targets that have no label.
+pub(crate) fn resolve_label(label: &str, ctx: &LineContext) -> Result<u32, MipsError> {
+	if let Some(addr) = ctx.labels.get(label) {
+		return Ok(*addr);
+	}
+	if let Some(addr) = parse_number(label).filter(|a| (0..=u32::MAX as i64).contains(a)) {
+		return Ok(addr as u32);
+	}
+	match ctx.pass {
+		AssemblerPass::Symbols => { Ok(ctx.address) }
+		AssemblerPass::Encode => { Err(MipsError::UndefinedLabel(String::from(label), ctx.line_number)) }
+	}
+}
+
+/// Converts a label into the signed 16 bit word offset used by `beq` and `bne`.
+/// The offset is counted from the instruction following the branch.
+fn branch_offset(label: &str, ctx: &LineContext) -> Result<u16, MipsError> {
+	let target = resolve_label(label, ctx)?;
+	let distance = target as i64 - (ctx.address as i64 + 4);
+	let offset = distance / 4;
+	if distance % 4 != 0 || offset < i16::MIN as i64 || offset > i16::MAX as i64 {
+		return Err(MipsError::BranchOutOfRange(String::from(label), ctx.line_number));
+	}
+	Ok(offset as u16)
+}
+
+/// Converts a label into the 26 bit word index used by `j` and `jal`.
+/// The upper 4 bits of the address come from the PC, so the target has to
+/// sit in the same 256MB region as the jump.
+fn jump_target(label: &str, ctx: &LineContext) -> Result<u32, MipsError> {
+	let target = resolve_label(label, ctx)?;
+	let region = ctx.address.wrapping_add(4) & 0xF0000000;
+	if target % 4 != 0 || target & 0xF0000000 != region {
+		return Err(MipsError::BranchOutOfRange(String::from(label), ctx.line_number));
+	}
+	Ok((target >> 2) & 0x03FFFFFF)
+}
+
+/// Reads the label a branch or jump goes to, or the address written as a number
+fn read_label<'s>(terms: &mut Operands<'_, 's>, ctx: &LineContext) -> Result<&'s str, MipsError> {
+	match terms.next() {
+		Some(Operand { kind: OperandKind::Expression(_), text }) => { Ok(*text) }
+		_ => { Err(MipsError::SyntaxError(ctx.line_number)) }
+	}
+}
+
+/// Reads the `$rs, $rt` pair at the front of a conditional branch
+fn load_branch_terms(terms: &mut Operands) -> Option<(usize, usize)> {
+	let rs = next_register(terms)?;
+	let rt = next_register(terms)?;
+	Some((rs, rt))
+}
+
+/// Reads the `$rt, $rs` pair at the front of an I-type instruction
+fn load_2_terms_immediate(terms: &mut Operands) -> Option<(usize, usize)> {
+	let rt = next_register(terms)?;
+	let rs = next_register(terms)?;
+	Some((rt, rs))
+}
+
+/// The expression an operand holds, when it is nothing more than that
+fn read_expression<'a, 's>(operand: Option<&'a Operand<'s>>, ctx: &LineContext) -> Result<&'a Expression<'s>, MipsError> {
+	match operand {
+		Some(Operand { kind: OperandKind::Expression(expr), .. }) => { Ok(expr) }
+		_ => { Err(MipsError::SyntaxError(ctx.line_number)) }
+	}
+}
+
+/// Reads the 16 bit immediate of an I-type instruction.
+/// The value can be any constant expression, and has to fit the range
+/// the hardware will extend it from.
+fn read_immediate(terms: &mut Operands, kind: ImmediateKind, ctx: &LineContext) -> Result<u16, MipsError> {
+	let value = expression::evaluate(read_expression(terms.next(), ctx)?, ctx)?;
+	let (min, max) = match kind {
+		ImmediateKind::SignExtended => { (i16::MIN as i64, i16::MAX as i64) }
+		ImmediateKind::ZeroExtended => { (0, u16::MAX as i64) }
+	};
+	if value < min || value > max {
+		return Err(MipsError::ImmediateOutOfRange(value, ctx.line_number));
+	}
+	Ok(value as u16)
+}
+
+/// Reads the `$rd, $rt, shamt` operands of the constant shifts
+fn read_shift_terms(op: u32, terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+	let (Some(rd), Some(rt)) = (next_register(terms), next_register(terms)) else {
+		return Err(MipsError::SyntaxError(ctx.line_number));
+	};
+	let amount = expression::evaluate(read_expression(terms.next(), ctx)?, ctx)?;
+	if !(0..32).contains(&amount) {
+		return Err(MipsError::ImmediateOutOfRange(amount, ctx.line_number));
+	}
+	Ok(DecodedInstruction::r(op, 0, rt, rd, amount as u32))
+}
+
+/// Drops a trailing `# comment` from a line.
+/// A '#' inside a string or character literal is kept.
+pub(crate) fn strip_comment(line: &str) -> &str {
+	let mut quote = None;
+	let mut escaped = false;
+	for (idx, c) in line.char_indices() {
+		match quote {
+			Some(q) => {
+				if escaped {
+					escaped = false;
+				} else if c == '\\' {
+					escaped = true;
+				} else if c == q {
+					quote = None;
+				}
+			}
+			None => {
+				if c == '#' {
+					return line[..idx].trim_end();
+				}
+				if c == '"' || c == '\'' {
+					quote = Some(c);
+				}
+			}
+		}
+	}
+	line
+}
+
+/// Splits the `label:` definitions off the front of a line.
+/// Names are letters, digits, '_', '.' and '$', and can't start with a digit
+/// unless they are all digits, like the numeric local label `1:`.
+/// Returns the names and the rest of the line.
+pub(crate) fn split_labels(line: &str) -> (Vec<&str>, &str) {
+	let mut names = vec![];
+	let mut rest = line.trim_start();
+	while let Some(idx) = rest.find(':') {
+		let name = &rest[..idx];
+		let valid = name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '$')
+			&& match name.chars().next() {
+				Some(c) if c.is_ascii_digit() => { name.chars().all(|c| c.is_ascii_digit()) }
+				Some(_) => { true }
+				None => { false }
+			};
+		if !valid {
+			break;
+		}
+		names.push(name);
+		rest = rest[idx + 1..].trim_start();
+	}
+	(names, rest)
+}
+
+/// How many bytes each value of `.word`, `.half` and `.byte` takes up
+fn integer_directive_size(name: &str) -> Option<u32> {
+	match name {
+		".word" => { Some(4) }
+		".half" | ".halfword" => { Some(2) }
+		".byte" => { Some(1) }
+		_ => { None }
+	}
+}
+
+/// Whether `bytes` more of static data fit before `DATA_LIMIT`
+fn fits_in_data(st: &PassState, bytes: i64) -> bool {
+	bytes >= 0 && st.data_pointer as i64 + bytes <= memory::DATA_LIMIT as i64
+}
+
+/// Reads the values of a data directive, each one with `read`.
+/// Any value can be written `value:count` to repeat it, like `.word 0:64`.
+/// Returns each value with how many times it repeats.
+fn read_data_list<T>(operands: &[Operand], ctx: &LineContext,
+					 read: fn(&Expression, &LineContext) -> Result<T, MipsError>) -> Result<Vec<(T, usize)>, MipsError> {
+	if operands.is_empty() {
+		return Err(MipsError::SyntaxError(ctx.line_number));
+	}
+	let mut values = vec![];
+	for operand in operands {
+		let (value, count) = match &operand.kind {
+			OperandKind::Expression(value) => { (value, 1) }
+			OperandKind::Repeated(value, count) => {
+				let count = expression::evaluate_constant(count, ctx)?;
+				if count < 0 || count > (memory::DATA_LIMIT - memory::DATA_BASE) as i64 {
+					return Err(MipsError::ImmediateOutOfRange(count, ctx.line_number));
+				}
+				(value, count as usize)
+			}
+			_ => { return Err(MipsError::SyntaxError(ctx.line_number)); }
+		};
+		values.push((read(value, ctx)?, count));
+	}
+	Ok(values)
+}
+
+/// Reads the value of `.float` or `.double`, like `3.14`, `-1.5e3` or `inf`.
+/// Plain constant expressions work too.
+fn read_float(value: &Expression, ctx: &LineContext) -> Result<f64, MipsError> {
+	match value.text.parse::<f64>() {
+		Ok(v) => { Ok(v) }
+		Err(_) => { Ok(expression::evaluate_constant(value, ctx)? as f64) }
+	}
+}
+
+/// Reads the power of two `.align` pads to, up to a double word
+fn read_alignment(operands: &[Operand], ctx: &LineContext) -> Result<u32, MipsError> {
+	if operands.len() != 1 {
+		return Err(MipsError::SyntaxError(ctx.line_number));
+	}
+	let power = expression::evaluate_constant(read_expression(operands.first(), ctx)?, ctx)?;
+	if !(0..=3).contains(&power) {
+		return Err(MipsError::ImmediateOutOfRange(power, ctx.line_number));
+	}
+	Ok(1 << power)
+}
+
+/// Every use of $at in an instruction's operands, as it was written
+fn reserved_registers<'s>(operands: &[Operand<'s>]) -> Vec<&'s str> {
+	operands.iter().filter_map(|operand| match operand.kind {
+		OperandKind::Register(1) => { Some(operand.text) }
+		OperandKind::Memory(_, 1) => { operand.base_register() }
+		_ => { None }
+	}).collect()
+}
+
+/// Reads the memory operand of a load or store, in any of the forms
+/// `offset($reg)`, `($reg)`, `label`, `label+const` or `label($reg)`.
+/// Returns the base register and the offset.
+fn read_memory_operand(terms: &mut Operands, ctx: &LineContext) -> Result<(usize, u16), MipsError> {
+	let (offset, base) = match terms.next().map(|o| &o.kind) {
+		Some(OperandKind::Memory(offset, base)) => { (offset.as_ref(), *base) }
+		Some(OperandKind::Expression(offset)) => { (Some(offset), 0) }
+		_ => { return Err(MipsError::SyntaxError(ctx.line_number)); }
+	};
+	let offset = match offset {
+		Some(offset) => { expression::evaluate(offset, ctx)? }
+		None => { 0 }
+	};
+	if offset < i16::MIN as i64 || offset > i16::MAX as i64 {
+		return Err(MipsError::ImmediateOutOfRange(offset, ctx.line_number));
+	}
+	Ok((base, offset as u16))
+}
+
+/// Turns source into a `Program`, keeping what it has placed so far while it works
+pub struct Assembler {
+	memory: Memory,					// the segments being filled
+	listing: Listing,
+	source_map: SourceMap,
+	diagnostics: Vec<Diagnostic>,	// errors and warnings found so far
+}
+
+impl Default for Assembler {
+	fn default() -> Assembler {
+		Assembler::new()
+	}
+}
+
+impl Assembler {
+	pub fn new() -> Assembler {
+		Assembler {
+			memory: Memory::new(),
+			listing: Listing::new(),
+			source_map: SourceMap::new(),
+			diagnostics: vec![],
+		}
+	}
+
+	/// Assembles source that isn't in a file, so `.include` paths are relative to the working directory
+	pub fn assemble(&mut self, contents: &str) -> Result<Program, MipsError> {
+		self.assemble_source(contents, None)
+	}
+
+	pub fn assemble_file(&mut self, filename: &str) -> Result<Program, MipsError> {
+		let Ok(contents) = fs::read_to_string(filename) else {
+			return Err(MipsError::FileNotFound);
+		};
+		self.assemble_source(&contents, Some(Path::new(filename)))
+	}
+
+	/// `file` is where `contents` came from, `.include` paths are relative to it.
+	/// When anything is wrong every error and warning comes back in `MipsError::Diagnostics`.
+	fn assemble_source(&mut self, contents: &str, file: Option<&Path>) -> Result<Program, MipsError> {
+		*self = Assembler::new();
+
+		let lines = match preprocessor::preprocess(contents, file) {
+			Ok(lines) => { lines }
+			Err(e) => {
+				self.diagnostics.push(Diagnostic::from_error(e));
+				return Err(MipsError::Diagnostics(mem::take(&mut self.diagnostics)));
+			}
+		};
+
+		// the first pass only exists to find where every label lands,
+		// so branches and jumps can point further down the file.
+		// Anything wrong with a line turns up again in the second pass, so it is reported there.
+		let mut labels: HashMap<String, u32> = HashMap::new();
+		self.assemble_pass(&lines, AssemblerPass::Symbols, &mut labels);
+		self.memory = Memory::new();
+		self.assemble_pass(&lines, AssemblerPass::Encode, &mut labels);
+
+		let entry = match labels.get("main") {
+			Some(addr) => { *addr }
+			None => {
+				self.diagnostics.push(Diagnostic::from_error(MipsError::MissingMain));
+				0
+			}
+		};
+		if self.diagnostics.iter().any(|d| d.is_error()) {
+			return Err(MipsError::Diagnostics(mem::take(&mut self.diagnostics)));
+		}
+		Ok(Program {
+			image: Image {
+				segments: self.memory.segments(),
+				entry,
+				symbols: labels,
+				endian: self.memory.endian(),
+			},
+			source_map: mem::take(&mut self.source_map),
+			diagnostics: mem::take(&mut self.diagnostics),
+			listing: mem::replace(&mut self.listing, Listing::new()),
+		})
+	}
+
+	fn make_add(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_ADD, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_addi(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_ADDI, rs, rt, read_immediate(terms, ImmediateKind::SignExtended, ctx)?))
+	}
+
+	fn make_addiu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_ADDIU, rs, rt, read_immediate(terms, ImmediateKind::SignExtended, ctx)?))
+	}
+
+	fn make_addu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_ADDU, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_sub(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_SUB, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_subu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_SUBU, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_and(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_AND, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_andi(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_ANDI, rs, rt, read_immediate(terms, ImmediateKind::ZeroExtended, ctx)?))
+	}
+
+	fn make_or(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_OR, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_ori(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_ORI, rs, rt, read_immediate(terms, ImmediateKind::ZeroExtended, ctx)?))
+	}
+
+	fn make_xor(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_XOR, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_xori(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_XORI, rs, rt, read_immediate(terms, ImmediateKind::ZeroExtended, ctx)?))
+	}
+
+	fn make_nor(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_NOR, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_sll(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		read_shift_terms(instruction::OP_SLL, terms, ctx)
+	}
+
+	fn make_srl(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		read_shift_terms(instruction::OP_SRL, terms, ctx)
+	}
+
+	fn make_sra(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		read_shift_terms(instruction::OP_SRA, terms, ctx)
+	}
+
+	fn make_sllv(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_shift_variable_terms(instruction::OP_SLLV, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_srlv(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_shift_variable_terms(instruction::OP_SRLV, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_srav(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_shift_variable_terms(instruction::OP_SRAV, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_slt(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_SLT, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_slti(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_SLTI, rs, rt, read_immediate(terms, ImmediateKind::SignExtended, ctx)?))
+	}
+
+	fn make_sltu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_3_terms(instruction::OP_SLTU, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_sltiu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rt, rs)) = load_2_terms_immediate(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_SLTIU, rs, rt, read_immediate(terms, ImmediateKind::SignExtended, ctx)?))
+	}
+
+	fn make_mult(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_2_terms(instruction::OP_MULT, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_multu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_2_terms(instruction::OP_MULTU, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_div(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_2_terms(instruction::OP_DIV, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_divu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(inst) = load_2_terms(instruction::OP_DIVU, terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(inst)
+	}
+
+	fn make_mfhi(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rd) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::r(instruction::OP_MFHI, 0, 0, rd, 0))
+	}
+
+	fn make_mthi(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rs) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::r(instruction::OP_MTHI, rs, 0, 0, 0))
+	}
+
+	fn make_mflo(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rd) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::r(instruction::OP_MFLO, 0, 0, rd, 0))
+	}
+
+	fn make_mtlo(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rs) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::r(instruction::OP_MTLO, rs, 0, 0, 0))
+	}
+
+	fn make_lui(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::i(instruction::OP_LUI, 0, rt, read_immediate(terms, ImmediateKind::ZeroExtended, ctx)?))
+	}
+
+	// memory related
+	fn make_lw(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_LW, base, rt, offset))
+	}
+
+	fn make_lh(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_LH, base, rt, offset))
+	}
+
+	fn make_lhu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_LHU, base, rt, offset))
+	}
+
+	fn make_lb(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_LB, base, rt, offset))
+	}
+
+	fn make_lbu(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_LBU, base, rt, offset))
+	}
+
+	fn make_sw(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_SW, base, rt, offset))
+	}
+
+	fn make_sh(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_SH, base, rt, offset))
+	}
+
+	fn make_sb(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rt) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let (base, offset) = read_memory_operand(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_SB, base, rt, offset))
+	}
+
+	fn make_beq(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rs, rt)) = load_branch_terms(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let label = read_label(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_BEQ, rs, rt, branch_offset(label, ctx)?))
+	}
+
+	fn make_bne(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some((rs, rt)) = load_branch_terms(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		let label = read_label(terms, ctx)?;
+		Ok(DecodedInstruction::i(instruction::OP_BNE, rs, rt, branch_offset(label, ctx)?))
+	}
+
+	fn make_jr(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let Some(rs) = next_register(terms) else {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		};
+		Ok(DecodedInstruction::r(instruction::OP_JR, rs, 0, 0, 0))
+	}
+
+	fn make_j(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let label = read_label(terms, ctx)?;
+		Ok(DecodedInstruction::j(instruction::OP_J, jump_target(label, ctx)?))
+	}
+
+	fn make_jal(terms: &mut Operands, ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		let label = read_label(terms, ctx)?;
+		Ok(DecodedInstruction::j(instruction::OP_JAL, jump_target(label, ctx)?))
+	}
+
+	fn make_syscall(_terms: &mut Operands, _ctx: &LineContext) -> Result<DecodedInstruction, MipsError> {
+		Ok(DecodedInstruction::r(instruction::OP_SYSCALL, 0, 0, 0, 0))
+	}
+
+	fn get_byte_segment_u32(line: u32, idx: usize) -> u8 {
+		let res = match idx {
+			0 => { (line & 0b11111111000000000000000000000000) >> 24 },
+			1 => { (line & 0b00000000111111110000000000000000) >> 16 },
+			2 => { (line & 0b00000000000000001111111100000000) >> 8 },
+			3 => { line & 0b00000000000000000000000011111111 },
+			_ => { 0 /* THIS SHOULD NEVER BE REACHED*/ }
+		} as u8;
+		// rust refuses to handle this unless the data is named
+		// so we're naming it just to return it
+		res
+	}
+
+	fn get_byte_segment_u16(line: u16, idx: usize) -> u8 {
+		let res = match idx {
+			0 => { (line & 0b1111111100000000) >> 8 },
+			1 => { line & 0b0000000011111111 },
+			_ => { 0 /* THIS SHOULD NEVER BE REACHED*/ }
+		} as u8;
+		// rust refuses to handle this unless the data is named
+		// so we're naming it just to return it
+		res
+	}
+
+	/// Appends one byte to the data segment
+	fn push_data_byte(&mut self, st: &mut PassState, b: u8) {
+		st.data_pointer = self.memory.push_data(b) + 1;
+		st.pending_labels.clear();
+	}
+
+	/// Appends one instruction to the text segment
+	fn push_instruction(&mut self, st: &mut PassState, word: u32) {
+		st.text_pointer = self.memory.push_text(word) + 4;
+		st.pending_labels.clear();
+	}
+
+	/// Pads the current segment up to a multiple of `size` bytes,
+	/// with zeros in the data and `nop`s in the text.
+	/// Labels right before the padding move past it, onto the value that follows.
+	fn align_data(&mut self, st: &mut PassState, labels: &mut HashMap<String, u32>, size: u32) {
+		let pending = std::mem::take(&mut st.pending_labels);
+		while st.address() % size != 0 {
+			match st.state {
+				LoadingState::Code => { self.push_instruction(st, 0); }
+				_ => { self.push_data_byte(st, 0); }
+			}
+		}
+		for name in pending.iter() {
+			labels.insert(name.clone(), st.address());
+		}
+		st.pending_labels = pending;
+		st.line_start = st.address();
+	}
+
+	/// Encodes a single real (not pseudo) instruction into its machine word
+	fn encode_instruction(op: &str, operands: &[Operand], ctx: &LineContext) -> Result<u32, MipsError> {
+		let Some(opcode) = OPName::from(op) else {
+			return Err(MipsError::UnknownMnemonic(String::from(op), ctx.line_number));
+		};
+		let func = match opcode {
+			instruction::OP_ADD => { Assembler::make_add }
+			instruction::OP_ADDI => { Assembler::make_addi }
+			instruction::OP_ADDIU => { Assembler::make_addiu }
+			instruction::OP_ADDU => { Assembler::make_addu }
+			instruction::OP_SUB => { Assembler::make_sub }
+			instruction::OP_SUBU => { Assembler::make_subu }
+			instruction::OP_AND => { Assembler::make_and }
+			instruction::OP_ANDI => { Assembler::make_andi }
+			instruction::OP_OR => { Assembler::make_or }
+			instruction::OP_ORI => { Assembler::make_ori }
+			instruction::OP_XOR => { Assembler::make_xor }
+			instruction::OP_XORI => { Assembler::make_xori }
+			instruction::OP_NOR => { Assembler::make_nor }
+			instruction::OP_SLL => { Assembler::make_sll }
+			instruction::OP_SRL => { Assembler::make_srl }
+			instruction::OP_SRA => { Assembler::make_sra }
+			instruction::OP_SLLV => { Assembler::make_sllv }
+			instruction::OP_SRLV => { Assembler::make_srlv }
+			instruction::OP_SRAV => { Assembler::make_srav }
+			instruction::OP_SLT => { Assembler::make_slt }
+			instruction::OP_SLTI => { Assembler::make_slti }
+			instruction::OP_SLTU => { Assembler::make_sltu }
+			instruction::OP_SLTIU => { Assembler::make_sltiu }
+			instruction::OP_MULT => { Assembler::make_mult }
+			instruction::OP_MULTU => { Assembler::make_multu }
+			instruction::OP_DIV => { Assembler::make_div }
+			instruction::OP_DIVU => { Assembler::make_divu }
+			instruction::OP_MFHI => { Assembler::make_mfhi }
+			instruction::OP_MTHI => { Assembler::make_mthi }
+			instruction::OP_MFLO => { Assembler::make_mflo }
+			instruction::OP_MTLO => { Assembler::make_mtlo }
+			instruction::OP_LUI => { Assembler::make_lui }
+			// memory related
+			instruction::OP_LW => { Assembler::make_lw }
+			instruction::OP_LH => { Assembler::make_lh }
+			instruction::OP_LHU => { Assembler::make_lhu }
+			instruction::OP_LB => { Assembler::make_lb }
+			instruction::OP_LBU => { Assembler::make_lbu }
+			instruction::OP_SW => { Assembler::make_sw }
+			instruction::OP_SH => { Assembler::make_sh }
+			instruction::OP_SB => { Assembler::make_sb }
+			// branching
+			instruction::OP_BEQ => { Assembler::make_beq }
+			instruction::OP_BNE => { Assembler::make_bne }
+			instruction::OP_JR => { Assembler::make_jr }
+			instruction::OP_J => { Assembler::make_j }
+			instruction::OP_JAL => { Assembler::make_jal }
+			instruction::OP_SYSCALL => { Assembler::make_syscall }
+			_ => { return Err(MipsError::UnknownInstruction(opcode)); }
+		};
+		let mut terms = operands.iter();
+		let inst = func(&mut terms, ctx)?;
+		// anything left over is more than the instruction takes
+		if terms.next().is_some() {
+			return Err(MipsError::SyntaxError(ctx.line_number));
+		}
+		Ok(inst.encode())
+	}
+
+	/// Assembles every line, carrying on past the ones that fail.
+	/// The errors are only kept from the second pass.
+	fn assemble_pass(&mut self, lines: &[SourceLine], pass: AssemblerPass,
+					 labels: &mut HashMap<String, u32>) {
+		let mut st = PassState {
+			state: LoadingState::FileOpen,
+			variables: HashMap::new(),
+			data_pointer: memory::DATA_BASE,
+			text_pointer: memory::TEXT_BASE,
+			pending_labels: vec![],
+			line_start: 0,
+			instructions: vec![],
+			last_label: None,
+			defined: HashSet::new(),
+		};
+		for source in lines {
+			st.line_start = st.address();
+			st.instructions.clear();
+			let result = self.assemble_line(source, pass, labels, &mut st);
+			if pass == AssemblerPass::Encode {
+				match result {
+					Ok(_) => { self.record_line(source, &st); }
+					Err(e) => { self.diagnostics.push(Diagnostic::on_line(Severity::Error, e, source)); }
+				}
+			}
+		} // for each line
+	}
+
+	/// Adds whatever the line placed in memory to the listing and the source map
+	fn record_line(&mut self, source: &SourceLine, st: &PassState) {
+		let segment = match st.state {
+			LoadingState::Code => { Segment::Text }
+			_ => { Segment::Data }
+		};
+		let entry = |address: u32, size: u32, basic: Option<String>| ListingEntry {
+			segment,
+			address,
+			size,
+			basic,
+			source: String::from(source.original.trim()),
+			file: source.file.clone(),
+			line_number: source.line_number,
+		};
+		if !st.instructions.is_empty() {
+			for (i, inst) in st.instructions.iter().enumerate() {
+				self.listing.add(entry(st.line_start + 4 * i as u32, 4, Some(inst.clone())));
+			}
+		} else if st.address() > st.line_start {
+			self.listing.add(entry(st.line_start, st.address() - st.line_start, None));
+		}
+
+		let text = source.original.as_str();
+		let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+		self.source_map.add(st.line_start, st.address() - st.line_start, SourceLocation {
+			file: source.file.clone(),
+			line: source.line_number,
+			column: indent + 1,
+			text: String::from(text),
+			label: st.last_label.clone(),
+		});
+	}
+
+	fn assemble_line(&mut self, source: &SourceLine, pass: AssemblerPass,
+					 labels: &mut HashMap<String, u32>, st: &mut PassState) -> Result<(), MipsError> {
+		let line_number = source.line_number;
+		let line = parser::parse_line(&source.text, line_number)?;
+		// labels come first, and can share the line with a statement
+		for name in line.labels {
+			if name == "main" && st.address() % 4 != 0 {
+				return Err(MipsError::InvalidMain);
+			}
+			if !st.defined.insert(String::from(name)) {
+				return Err(MipsError::DuplicateLabel(String::from(name), line_number));
+			}
+			labels.insert(String::from(name), st.address());
+			if pass == AssemblerPass::Encode {
+				self.listing.define(name, source);
+			}
+			// the names numeric labels are given don't mean anything to whoever wrote them
+			if !preprocessor::is_renamed_local_label(name) {
+				st.last_label = Some(String::from(name));
+			}
+			st.pending_labels.push(String::from(name));
+		}
+		let Some(statement) = line.statement else {
+			return Ok(());
+		};
+
+		// either segment can be picked up again at any point, carrying on where it left off
+		if let Statement::Directive(name @ (".data" | ".text"), _) = statement {
+			st.state = if name == ".data" { LoadingState::Data } else { LoadingState::Code };
+			st.line_start = st.address();
+			st.last_label = None;
+			return Ok(());
+		}
+		// there is only ever one file, so there is nothing to export or import
+		if let Statement::Directive(".globl" | ".extern", _) = statement {
+			return Ok(());
+		}
+
+		match st.state {
+			LoadingState::FileOpen => {
+				let Statement::Assignment(name, value) = statement else {
+					return Err(MipsError::SyntaxError(line_number));
+				};
+				let ctx = LineContext {
+					labels,
+					variables: &st.variables,
+					address: st.data_pointer,
+					line_number,
+					pass,
+				};
+				let val = expression::evaluate(&value, &ctx)?;
+				if val < i32::MIN as i64 || val > u32::MAX as i64 {
+					return Err(MipsError::ImmediateOutOfRange(val, line_number));
+				}
+				st.variables.insert(String::from(name), val as i32);
+			}
+
+			LoadingState::Data => {
+				// only directives place anything in the data
+				match statement {
+					Statement::Directive(name, operands) => {
+						self.assemble_data(name, &operands, pass, labels, st, line_number)?;
+					}
+					Statement::Instruction(op, _) => {
+						return Err(MipsError::InstructionInData(String::from(op), line_number));
+					}
+					Statement::Assignment(name, _) => {
+						return Err(MipsError::UnknownMnemonic(String::from(name), line_number));
+					}
+				}
+			}
+
+			LoadingState::Code => {
+				let ctx = LineContext {
+					labels,
+					variables: &st.variables,
+					address: st.text_pointer,
+					line_number,
+					pass,
+				};
+				match statement {
+					Statement::Directive(".align", operands) => {
+						let size = read_alignment(&operands, &ctx)?;
+						self.align_data(st, labels, size);
+					}
+					Statement::Instruction(op, operands) => {
+						if pass == AssemblerPass::Encode {
+							for name in reserved_registers(&operands) {
+								let warning = MipsError::ReservedRegister(String::from(name), line_number);
+								self.diagnostics.push(Diagnostic::on_line(Severity::Warning, warning, source));
+							}
+						}
+						let (words, instructions) = Assembler::encode_statement(op, &operands, &ctx)?;
+						for word in words {
+							self.push_instruction(st, word);
+						}
+						st.instructions = instructions;
+					}
+					// constants only go before the first segment
+					Statement::Directive(name, _) | Statement::Assignment(name, _) => {
+						return Err(MipsError::UnknownMnemonic(String::from(name), line_number));
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Encodes an instruction, expanding it first if it is a pseudo instruction.
+	/// Returns the machine words with the real instruction each one came from.
+	fn encode_statement(op: &str, operands: &[Operand], ctx: &LineContext) -> Result<(Vec<u32>, Vec<String>), MipsError> {
+		let expansion = if pseudo::is_pseudo(op) {
+			Some(pseudo::expand(op, operands, ctx)?)
+		} else {
+			pseudo::expand_memory_operand(op, operands, ctx)?
+		};
+		let Some(expansion) = expansion else {
+			let texts: Vec<&str> = operands.iter().map(|o| o.text).collect();
+			let basic = if texts.is_empty() { String::from(op) } else { format!("{} {}", op, texts.join(", ")) };
+			return Ok((vec![Assembler::encode_instruction(op, operands, ctx)?], vec![basic]));
+		};
+		// each real instruction is encoded at its own address,
+		// so branches inside the expansion get the right offset
+		let mut words = Vec::with_capacity(expansion.len());
+		for (i, real) in expansion.iter().enumerate() {
+			let ctx = LineContext { address: ctx.address + 4 * i as u32, ..*ctx };
+			let line = parser::parse_line(real, ctx.line_number)?;
+			let Some(Statement::Instruction(op, operands)) = line.statement else {
+				return Err(MipsError::SyntaxError(ctx.line_number));
+			};
+			words.push(Assembler::encode_instruction(op, &operands, &ctx)?);
+		}
+		Ok((words, expansion))
+	}
+
+	/// Places the values of a data directive
+	fn assemble_data(&mut self, name: &str, operands: &[Operand], pass: AssemblerPass,
+					 labels: &mut HashMap<String, u32>, st: &mut PassState, line_number: usize) -> Result<(), MipsError> {
+		let ctx = LineContext {
+			labels,
+			variables: &st.variables,
+			address: st.data_pointer,
+			line_number,
+			pass,
+		};
+		match name {
+			".align" => {
+				let size = read_alignment(operands, &ctx)?;
+				self.align_data(st, labels, size);
+			}
+			".space" => { /* 8 bits times the size */
+				if operands.len() != 1 {
+					return Err(MipsError::SyntaxError(line_number));
+				}
+				// the size has to be known in the first pass, so it can't use labels
+				let val = expression::evaluate_constant(read_expression(operands.first(), &ctx)?, &ctx)?;
+				if !fits_in_data(st, val) {
+					return Err(MipsError::ImmediateOutOfRange(val, line_number));
+				}
+				for _ in 0..val as usize {
+					self.push_data_byte(st, 0);
+				}
+			}
+			".word" | ".half" | ".halfword" | ".byte" => {
+				let size = integer_directive_size(name).unwrap_or(1);
+				// words and halfwords land on their natural boundary
+				self.align_data(st, labels, size);
+				let ctx = LineContext {
+					labels,
+					variables: &st.variables,
+					address: st.data_pointer,
+					line_number,
+					pass,
+				};
+				let values = read_data_list(operands, &ctx, expression::evaluate)?;
+				let (min, max) = match size {
+					4 => { (i32::MIN as i64, u32::MAX as i64) }
+					2 => { (i16::MIN as i64, u16::MAX as i64) }
+					_ => { (i8::MIN as i64, u8::MAX as i64) }
+				};
+				for (val, count) in values {
+					// labels are only placeholders until the second pass
+					if pass == AssemblerPass::Encode && (val < min || val > max) {
+						return Err(MipsError::ImmediateOutOfRange(val, line_number));
+					}
+					if !fits_in_data(st, count as i64 * size as i64) {
+						return Err(MipsError::ImmediateOutOfRange(count as i64, line_number));
+					}
+					for _ in 0..count {
+						for i in 0..size as usize {
+							let b = match size {
+								4 => { Assembler::get_byte_segment_u32(val as u32, i) }
+								2 => { Assembler::get_byte_segment_u16(val as u16, i) }
+								_ => { val as u8 }
+							};
+							self.push_data_byte(st, b);
+						}
+					}
+				}
+			}
+			".ascii" | ".asciiz" => { /* 8 bits per character, .asciiz adds a NULL */
+				let [Operand { kind: OperandKind::String(bytes), .. }] = operands else {
+					return Err(MipsError::SyntaxError(line_number));
+				};
+				let mut bytes = bytes.clone();
+				if name == ".asciiz" {
+					bytes.push(0);
+				}
+				for b in bytes {
+					self.push_data_byte(st, b);
+				}
+			}
+			".float" | ".double" => { /* IEEE-754 */
+				let double = name == ".double";
+				self.align_data(st, labels, if double { 8 } else { 4 });
+				let ctx = LineContext {
+					labels,
+					variables: &st.variables,
+					address: st.data_pointer,
+					line_number,
+					pass,
+				};
+				let values = read_data_list(operands, &ctx, read_float)?;
+				for (val, count) in values {
+					// doubles are written as their high word, then their low word
+					let words = if double {
+						let bits = val.to_bits();
+						vec![(bits >> 32) as u32, bits as u32]
+					} else {
+						if val.is_finite() && !(val as f32).is_finite() {
+							return Err(MipsError::InvalidExpression(val.to_string(), line_number));
+						}
+						vec![(val as f32).to_bits()]
+					};
+					if !fits_in_data(st, count as i64 * 4 * words.len() as i64) {
+						return Err(MipsError::ImmediateOutOfRange(count as i64, line_number));
+					}
+					for _ in 0..count {
+						for word in words.iter() {
+							for i in 0..4 {
+								self.push_data_byte(st, Assembler::get_byte_segment_u32(*word, i));
+							}
+						}
+					}
+				}
+			}
+			_ => { return Err(MipsError::UnknownMnemonic(String::from(name), line_number)); }
+		}
+		Ok(())
+	}
+}
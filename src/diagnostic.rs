@@ -0,0 +1,153 @@
+// Errors and warnings found while assembling, each pointing at the exact spot in the source.
+// They render the way rustc does:
+//
+//	error: unknown register `$t10`
+//	 --> data/game.asm:12:6
+//	   |
+//	12 | 	add $t10, $t1, $t2
+//	   | 	    ^^^^
+//	   = note: in macro `push` used at data/game.asm:20
+use std::fmt;
+
+use crate::assembler::strip_comment;
+use crate::mips_int::MipsError;
+use crate::preprocessor::SourceLine;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+/// A range of one line in a file
+#[derive(Debug, Clone)]
+pub struct Span {
+	pub file: Option<String>,	// None when the source wasn't read from a file
+	pub line: usize,			// 1 based
+	pub column: usize,			// 1 based, in characters
+	pub length: usize,			// in characters, at least 1
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub error: MipsError,		// what went wrong, without any file or macro wrapping
+	pub span: Option<Span>,
+	pub source: Option<String>,	// the text of the spanned line
+	pub notes: Vec<String>,
+}
+
+impl Severity {
+	fn name(&self) -> &'static str {
+		match self {
+			Severity::Error => { "error" }
+			Severity::Warning => { "warning" }
+		}
+	}
+}
+
+/// Finds `culprit` in the line, or falls back to the whole statement.
+/// Returns the 1 based column and the length, both in characters.
+fn locate_in_line(text: &str, culprit: Option<&str>) -> (usize, usize) {
+	let code = strip_comment(text);
+	let found = culprit
+		.filter(|c| !c.is_empty())
+		.and_then(|c| code.find(c).map(|idx| (idx, c.chars().count())));
+	let (start, length) = match found {
+		Some(found) => { found }
+		None => {
+			let start = code.len() - code.trim_start().len();
+			(start, code.trim().chars().count())
+		}
+	};
+	(text[..start].chars().count() + 1, length.max(1))
+}
+
+impl Diagnostic {
+	/// An error or warning about one line of the (preprocessed) source
+	pub fn on_line(severity: Severity, error: MipsError, source: &SourceLine) -> Diagnostic {
+		let (column, length) = locate_in_line(&source.text, error.culprit());
+		let notes = source.macro_stack.iter().rev()
+			.map(|call| {
+				let file = call.file.as_ref().map(|f| format!("{}:", f.display())).unwrap_or_default();
+				format!("in macro `{}` used at {}{}", call.name, file, call.line_number)
+			})
+			.collect();
+		Diagnostic {
+			severity,
+			error,
+			span: Some(Span {
+				file: source.file.as_ref().map(|f| f.display().to_string()),
+				line: source.line_number,
+				column,
+				length,
+			}),
+			source: Some(source.text.clone()),
+			notes,
+		}
+	}
+
+	/// Turns an error that was wrapped with the files and macros it came from
+	/// back into a diagnostic. There is no source text to show for these.
+	pub fn from_error(error: MipsError) -> Diagnostic {
+		let mut error = error;
+		let mut file = None;
+		let mut notes = vec![];
+		let error = loop {
+			error = match error {
+				MipsError::InFile(f, inner) => {
+					file = Some(f);
+					*inner
+				}
+				MipsError::InMacro(name, line, inner) => {
+					let at = file.take().map(|f| format!("{}:", f)).unwrap_or_default();
+					notes.insert(0, format!("in macro `{}` used at {}{}", name, at, line));
+					*inner
+				}
+				e => { break e; }
+			};
+		};
+		let span = error.line().map(|line| Span { file, line, column: 1, length: 1 });
+		Diagnostic {
+			severity: Severity::Error,
+			error,
+			span,
+			source: None,
+			notes,
+		}
+	}
+
+	pub fn is_error(&self) -> bool {
+		self.severity == Severity::Error
+	}
+
+	/// Renders the message, where it happened and a caret snippet of the line
+	pub fn render(&self) -> String {
+		let mut out = format!("{}: {}\n", self.severity.name(), self.error.message());
+		let gutter = self.span.as_ref().map(|s| s.line.to_string().len()).unwrap_or(0);
+		let pad = " ".repeat(gutter);
+		if let Some(span) = &self.span {
+			let file = span.file.as_ref().map(|f| format!("{}:", f)).unwrap_or_default();
+			out.push_str(&format!("{}--> {}{}:{}\n", pad, file, span.line, span.column));
+			if let Some(text) = &self.source {
+				// tabs are kept in front of the caret so it lines up however wide they show
+				let before: String = text.chars().take(span.column - 1)
+					.map(|c| if c == '\t' { '\t' } else { ' ' })
+					.collect();
+				out.push_str(&format!("{} |\n", pad));
+				out.push_str(&format!("{} | {}\n", span.line, text.trim_end()));
+				out.push_str(&format!("{} | {}{}\n", pad, before, "^".repeat(span.length)));
+			}
+		}
+		for note in self.notes.iter() {
+			out.push_str(&format!("{} = note: {}\n", pad, note));
+		}
+		out
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.render())
+	}
+}
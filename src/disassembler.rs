@@ -0,0 +1,163 @@
+// Turns machine words back into assembly, the reverse of the assembler.
+// Registers get their ABI names, and branch and jump targets are shown as
+// labels when the symbol table has one for them:
+//
+//	0x01095020	add $t2, $t0, $t1
+//	0x1500fffe	bne $t0, $zero, loop
+//	0xffffffff	.word 0xffffffff
+//
+// Anything it prints assembles back into the same word.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::instruction::{self, DecodedInstruction, OPName};
+use crate::register::ABI_NAMES;
+
+const RS: u32 = 0b00000011111000000000000000000000;
+const RT: u32 = 0b00000000000111110000000000000000;
+const RD: u32 = 0b00000000000000001111100000000000;
+const SHAMT: u32 = 0b00000000000000000000011111000000;
+
+/// How an instruction's operands are written
+#[derive(Copy, Clone)]
+enum Format {
+	Registers,		// $rd, $rs, $rt
+	Shift,			// $rd, $rt, shamt
+	ShiftVariable,	// $rd, $rt, $rs
+	MultDiv,		// $rs, $rt
+	MoveFrom,		// $rd
+	MoveTo,			// $rs
+	Signed,			// $rt, $rs, imm
+	Unsigned,		// $rt, $rs, imm
+	UpperImmediate,	// $rt, imm
+	Memory,			// $rt, imm($rs)
+	Branch,			// $rs, $rt, target
+	Jump,			// target
+	NoOperands,
+}
+
+impl Format {
+	fn of(op: u32) -> Format {
+		match op {
+			instruction::OP_SLL | instruction::OP_SRL | instruction::OP_SRA => { Format::Shift }
+			instruction::OP_SLLV | instruction::OP_SRLV | instruction::OP_SRAV => { Format::ShiftVariable }
+			instruction::OP_MULT | instruction::OP_MULTU |
+			instruction::OP_DIV | instruction::OP_DIVU => { Format::MultDiv }
+			instruction::OP_MFHI | instruction::OP_MFLO => { Format::MoveFrom }
+			instruction::OP_MTHI | instruction::OP_MTLO | instruction::OP_JR => { Format::MoveTo }
+			instruction::OP_ADDI | instruction::OP_ADDIU |
+			instruction::OP_SLTI | instruction::OP_SLTIU => { Format::Signed }
+			instruction::OP_ANDI | instruction::OP_ORI | instruction::OP_XORI => { Format::Unsigned }
+			instruction::OP_LUI => { Format::UpperImmediate }
+			instruction::OP_LW | instruction::OP_LH | instruction::OP_LHU | instruction::OP_LB |
+			instruction::OP_LBU | instruction::OP_SW | instruction::OP_SH | instruction::OP_SB => { Format::Memory }
+			instruction::OP_BEQ | instruction::OP_BNE => { Format::Branch }
+			instruction::OP_J | instruction::OP_JAL => { Format::Jump }
+			instruction::OP_SYSCALL => { Format::NoOperands }
+			_ => { Format::Registers }
+		}
+	}
+
+	/// The bits the assembler always leaves as 0 for this format.
+	/// A word with any of them set isn't something it could have written.
+	fn unused_bits(&self) -> u32 {
+		match self {
+			Format::Registers | Format::ShiftVariable => { SHAMT }
+			Format::Shift => { RS }
+			Format::MultDiv => { RD | SHAMT }
+			Format::MoveFrom => { RS | RT | SHAMT }
+			Format::MoveTo => { RT | RD | SHAMT }
+			Format::UpperImmediate => { RS }
+			Format::NoOperands => { RS | RT | RD | SHAMT }
+			_ => { 0 }
+		}
+	}
+}
+
+fn reg(word: u32, field: u32) -> String {
+	format!("${}", ABI_NAMES[((word & field) >> field.trailing_zeros()) as usize])
+}
+
+pub struct Disassembler {
+	names: BTreeMap<u32, String>,	// address to label
+}
+
+impl Default for Disassembler {
+	fn default() -> Disassembler {
+		Disassembler::new()
+	}
+}
+
+impl Disassembler {
+	/// A disassembler that shows every target as an address
+	pub fn new() -> Disassembler {
+		Disassembler {
+			names: BTreeMap::new(),
+		}
+	}
+
+	/// A disassembler that shows targets with a label by name.
+	/// When several labels share an address the first one alphabetically is used.
+	pub fn with_labels(labels: &HashMap<String, u32>) -> Disassembler {
+		let mut names: BTreeMap<u32, String> = BTreeMap::new();
+		for (name, addr) in labels.iter() {
+			if names.get(addr).is_none_or(|current| name < current) {
+				names.insert(*addr, name.clone());
+			}
+		}
+		Disassembler { names }
+	}
+
+	fn target(&self, addr: u32) -> String {
+		match self.names.get(&addr) {
+			Some(name) => { name.clone() }
+			None => { format!("{:#010x}", addr) }
+		}
+	}
+
+	/// Decodes one word into a line of assembly.
+	///
+	/// # Arguments
+	///
+	/// * `word` - The machine code
+	/// * `address` - Where the word sits, which branches and jumps are relative to
+	pub fn disassemble(&self, word: u32, address: u32) -> String {
+		let op = DecodedInstruction::decode(word).op();
+		let format = Format::of(op);
+		let Some(name) = OPName::name(op).filter(|_| word & format.unused_bits() == 0) else {
+			return format!(".word {:#010x}", word);
+		};
+		if word == 0 {
+			return String::from("nop");
+		}
+
+		let imm = word & 0xFFFF;
+		let simm = imm as u16 as i16;
+		match format {
+			Format::Registers => { format!("{} {}, {}, {}", name, reg(word, RD), reg(word, RS), reg(word, RT)) }
+			Format::Shift => { format!("{} {}, {}, {}", name, reg(word, RD), reg(word, RT), (word & SHAMT) >> 6) }
+			Format::ShiftVariable => { format!("{} {}, {}, {}", name, reg(word, RD), reg(word, RT), reg(word, RS)) }
+			Format::MultDiv => { format!("{} {}, {}", name, reg(word, RS), reg(word, RT)) }
+			Format::MoveFrom => { format!("{} {}", name, reg(word, RD)) }
+			Format::MoveTo => { format!("{} {}", name, reg(word, RS)) }
+			Format::Signed => { format!("{} {}, {}, {}", name, reg(word, RT), reg(word, RS), simm) }
+			Format::Unsigned => { format!("{} {}, {}, {}", name, reg(word, RT), reg(word, RS), imm) }
+			Format::UpperImmediate => { format!("{} {}, {}", name, reg(word, RT), imm) }
+			Format::Memory => { format!("{} {}, {}({})", name, reg(word, RT), simm, reg(word, RS)) }
+			Format::Branch => {
+				// counted in words from the instruction after the branch
+				let target = address.wrapping_add(4).wrapping_add((simm as i32 * 4) as u32);
+				format!("{} {}, {}, {}", name, reg(word, RS), reg(word, RT), self.target(target))
+			}
+			Format::Jump => {
+				let region = address.wrapping_add(4) & 0xF0000000;
+				format!("{} {}", name, self.target(region | (word & 0x03FFFFFF) << 2))
+			}
+			Format::NoOperands => { String::from(name) }
+		}
+	}
+}
+
+/// Decodes one word without a symbol table, see `Disassembler::disassemble`
+pub fn disassemble(word: u32, address: u32) -> String {
+	Disassembler::new().disassemble(word, address)
+}
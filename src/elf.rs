@@ -0,0 +1,394 @@
+// A minimal ELF32 executable for MIPS: one PT_LOAD program header per segment,
+// a section for each of them, and a symbol table with every label.
+// It has the byte order of the memory it came from.
+//
+// The loader also takes statically linked executables from a MIPS cross toolchain,
+// big or little endian. Anything that would need a dynamic linker or relocating
+// is turned down with an error saying why.
+//
+// Branches and jumps run like they do in MARS, without a delay slot. A toolchain
+// fills the slot after every branch with an instruction it expects to run before
+// the branch is taken, so its programs only behave the same here when every delay
+// slot holds a `nop` (`-fno-delayed-branch`, or `.set noreorder` with the nops written out).
+use std::collections::HashMap;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+
+use crate::image::{Image, Segment};
+use crate::memory::{self, Endian};
+use crate::mips_int::MipsError;
+
+const EHDR_SIZE: u32 = 52;
+const PHDR_SIZE: u32 = 32;
+const SHDR_SIZE: u32 = 40;
+const SYM_SIZE: u32 = 16;
+/// The most a segment can reserve past what the file holds, so a bad size can't use up all memory
+const MAX_ZERO_FILL: u32 = 64 << 20;
+
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+const EV_CURRENT: u8 = 1;
+const ET_REL: u16 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_MIPS: u16 = 8;
+/// MIPS32, o32 ABI
+const EF_MIPS: u32 = 0x50001000;
+const EF_MIPS_ARCH: u32 = 0xF0000000;
+/// Compressed instruction sets, which decode differently
+const EF_MIPS_MICROMIPS: u32 = 0x02000000;
+const EF_MIPS_ARCH_ASE_M16: u32 = 0x04000000;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+const SHF_WRITE: u32 = 1;
+const SHF_ALLOC: u32 = 2;
+const SHF_EXECINSTR: u32 = 4;
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xFFF1;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+/// A string table under construction
+struct StringTable {
+	bytes: Vec<u8>,
+}
+
+impl StringTable {
+	fn new() -> StringTable {
+		StringTable { bytes: vec![0] }
+	}
+
+	/// Adds a name, returning where it starts
+	fn add(&mut self, name: &str) -> u32 {
+		let idx = self.bytes.len() as u32;
+		self.bytes.extend_from_slice(name.as_bytes());
+		self.bytes.push(0);
+		idx
+	}
+}
+
+struct SectionHeader {
+	name: u32,
+	kind: u32,
+	flags: u32,
+	address: u32,
+	offset: u32,
+	size: u32,
+	link: u32,
+	info: u32,
+	align: u32,
+	entry_size: u32,
+}
+
+/// Pads the file out until the offset is a multiple of `align`
+fn pad_to(out: &mut Vec<u8>, align: usize) {
+	while out.len() % align != 0 {
+		out.push(0);
+	}
+}
+
+fn segment_name(segment: &Segment) -> String {
+	match segment.address {
+		memory::TEXT_BASE => { String::from(".text") }
+		memory::DATA_BASE => { String::from(".data") }
+		address => { format!(".load.{:08x}", address) }
+	}
+}
+
+/// Writes an image as an ELF32 executable
+pub fn write(image: &Image) -> Vec<u8> {
+	match image.endian {
+		Endian::Big => { write_as::<BigEndian>(image, ELFDATA2MSB) }
+		Endian::Little => { write_as::<LittleEndian>(image, ELFDATA2LSB) }
+	}
+}
+
+fn write_as<E: ByteOrder>(image: &Image, data_encoding: u8) -> Vec<u8> {
+	let mut shstrtab = StringTable::new();
+	let mut strtab = StringTable::new();
+	let mut sections = vec![SectionHeader {
+		name: 0, kind: 0, flags: 0, address: 0, offset: 0, size: 0, link: 0, info: 0, align: 0, entry_size: 0,
+	}];
+
+	// headers first, then the contents of every segment
+	let phnum = image.segments.len() as u32;
+	let mut out = vec![0; (EHDR_SIZE + phnum * PHDR_SIZE) as usize];
+	let mut phdrs = vec![];
+	for segment in image.segments.iter() {
+		// the offset in the file has to line up with the address
+		while out.len() % 4 != segment.address as usize % 4 {
+			out.push(0);
+		}
+		let offset = out.len() as u32;
+		out.extend_from_slice(&segment.bytes);
+		let code = segment.address < memory::DATA_BASE;
+		let size = segment.bytes.len() as u32;
+		phdrs.push((offset, segment.address, size, if code { PF_R | PF_X } else { PF_R | PF_W }));
+		sections.push(SectionHeader {
+			name: shstrtab.add(&segment_name(segment)),
+			kind: SHT_PROGBITS,
+			flags: if code { SHF_ALLOC | SHF_EXECINSTR } else { SHF_ALLOC | SHF_WRITE },
+			address: segment.address,
+			offset,
+			size,
+			link: 0,
+			info: 0,
+			align: 4,
+			entry_size: 0,
+		});
+	}
+
+	// locals have to come before globals, only `main` is global
+	let mut symbols: Vec<(&String, &u32)> = image.symbols.iter().collect();
+	symbols.sort_by(|a, b| (a.0 == "main").cmp(&(b.0 == "main")).then(a.1.cmp(b.1)).then(a.0.cmp(b.0)));
+	let first_global = 1 + symbols.iter().filter(|(name, _)| *name != "main").count() as u32;
+	pad_to(&mut out, 4);
+	let symtab_offset = out.len() as u32;
+	out.extend_from_slice(&[0; SYM_SIZE as usize]);
+	for (name, addr) in symbols.iter() {
+		let section = image.segments.iter()
+			.position(|s| **addr >= s.address && **addr - s.address < s.bytes.len() as u32)
+			.map_or(SHN_ABS, |idx| idx as u16 + 1);
+		let bind = if *name == "main" { STB_GLOBAL } else { STB_LOCAL };
+		let _ = out.write_u32::<E>(strtab.add(name));
+		let _ = out.write_u32::<E>(**addr);
+		let _ = out.write_u32::<E>(0);
+		out.push(bind << 4 | STT_NOTYPE);
+		out.push(0);
+		let _ = out.write_u16::<E>(section);
+	}
+	let symtab_size = out.len() as u32 - symtab_offset;
+	let symtab_idx = sections.len() as u32;
+	sections.push(SectionHeader {
+		name: shstrtab.add(".symtab"),
+		kind: SHT_SYMTAB,
+		flags: 0,
+		address: 0,
+		offset: symtab_offset,
+		size: symtab_size,
+		link: symtab_idx + 1,
+		info: first_global,
+		align: 4,
+		entry_size: SYM_SIZE,
+	});
+	// the section names include their own, so every name is added before it is written
+	let strtab_name = shstrtab.add(".strtab");
+	let shstrtab_name = shstrtab.add(".shstrtab");
+	for (name, table) in [(strtab_name, strtab.bytes), (shstrtab_name, shstrtab.bytes)] {
+		sections.push(SectionHeader {
+			name,
+			kind: SHT_STRTAB,
+			flags: 0,
+			address: 0,
+			offset: out.len() as u32,
+			size: table.len() as u32,
+			link: 0,
+			info: 0,
+			align: 1,
+			entry_size: 0,
+		});
+		out.extend_from_slice(&table);
+	}
+
+	pad_to(&mut out, 4);
+	let shoff = out.len() as u32;
+	for sh in sections.iter() {
+		for field in [sh.name, sh.kind, sh.flags, sh.address, sh.offset, sh.size, sh.link, sh.info, sh.align, sh.entry_size] {
+			let _ = out.write_u32::<E>(field);
+		}
+	}
+
+	// now that everything has a place, fill in the headers
+	let mut header = vec![0x7F, b'E', b'L', b'F', ELFCLASS32, data_encoding, EV_CURRENT, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+	let _ = header.write_u16::<E>(ET_EXEC);
+	let _ = header.write_u16::<E>(EM_MIPS);
+	let _ = header.write_u32::<E>(EV_CURRENT as u32);
+	let _ = header.write_u32::<E>(image.entry);
+	let _ = header.write_u32::<E>(EHDR_SIZE);
+	let _ = header.write_u32::<E>(shoff);
+	let _ = header.write_u32::<E>(EF_MIPS);
+	let _ = header.write_u16::<E>(EHDR_SIZE as u16);
+	let _ = header.write_u16::<E>(PHDR_SIZE as u16);
+	let _ = header.write_u16::<E>(phnum as u16);
+	let _ = header.write_u16::<E>(SHDR_SIZE as u16);
+	let _ = header.write_u16::<E>(sections.len() as u16);
+	let _ = header.write_u16::<E>(sections.len() as u16 - 1);
+	for (offset, address, size, flags) in phdrs {
+		for field in [PT_LOAD, offset, address, address, size, size, flags, 4] {
+			let _ = header.write_u32::<E>(field);
+		}
+	}
+	out[..header.len()].copy_from_slice(&header);
+	out
+}
+
+/// Reads the parts of the file the loader needs, failing on anything cut short
+struct Reader<'a> {
+	bytes: &'a [u8],
+	endian: Endian,
+}
+
+// Offsets are read as u32 but added up as u64, so nothing a file says can overflow them.
+// Anything past the end of the file is caught when it is read.
+impl<'a> Reader<'a> {
+	fn slice(&self, offset: u64, size: u32) -> Result<&'a [u8], MipsError> {
+		let end = offset + size as u64;
+		match usize::try_from(offset).ok().zip(usize::try_from(end).ok()).and_then(|(start, end)| self.bytes.get(start..end)) {
+			Some(bytes) => { Ok(bytes) }
+			None => { Err(MipsError::InvalidImage(String::from("the ELF file is cut short"))) }
+		}
+	}
+
+	fn u16(&self, offset: u64) -> Result<u16, MipsError> {
+		let bytes = self.slice(offset, 2)?;
+		Ok(match self.endian {
+			Endian::Big => { BigEndian::read_u16(bytes) }
+			Endian::Little => { LittleEndian::read_u16(bytes) }
+		})
+	}
+
+	fn u32(&self, offset: u64) -> Result<u32, MipsError> {
+		let bytes = self.slice(offset, 4)?;
+		Ok(match self.endian {
+			Endian::Big => { BigEndian::read_u32(bytes) }
+			Endian::Little => { LittleEndian::read_u32(bytes) }
+		})
+	}
+
+	/// The null terminated string starting at `offset`
+	fn string(&self, offset: u64) -> Result<String, MipsError> {
+		let Some(rest) = usize::try_from(offset).ok().and_then(|start| self.bytes.get(start..)) else {
+			return Err(MipsError::InvalidImage(String::from("the ELF file is cut short")));
+		};
+		let end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+		Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+	}
+}
+
+/// A name for the machines most likely to be loaded by mistake
+fn machine_name(machine: u16) -> &'static str {
+	match machine {
+		2 => { "SPARC" }
+		3 => { "x86" }
+		20 => { "PowerPC" }
+		40 => { "ARM" }
+		62 => { "x86-64" }
+		183 => { "AArch64" }
+		243 => { "RISC-V" }
+		_ => { "unknown" }
+	}
+}
+
+/// Reads a statically linked ELF32 MIPS executable, big or little endian:
+/// its loadable segments, entry point and symbols
+pub fn read(bytes: &[u8]) -> Result<Image, MipsError> {
+	let ident = Reader { bytes, endian: Endian::Big }.slice(0, 16)?;
+	if ident[..4] != [0x7F, b'E', b'L', b'F'] {
+		return Err(MipsError::InvalidImage(String::from("not an ELF file")));
+	}
+	match ident[4] {
+		ELFCLASS32 => {}
+		ELFCLASS64 => { return Err(MipsError::UnsupportedElf(String::from("only 32 bit ELF files can be loaded"))); }
+		_ => { return Err(MipsError::InvalidImage(String::from("unknown ELF class"))); }
+	}
+	let endian = match ident[5] {
+		ELFDATA2MSB => { Endian::Big }
+		ELFDATA2LSB => { Endian::Little }
+		_ => { return Err(MipsError::InvalidImage(String::from("unknown byte order"))); }
+	};
+	let file = Reader { bytes, endian };
+
+	let machine = file.u16(18)?;
+	if machine != EM_MIPS {
+		return Err(MipsError::UnsupportedElf(format!("machine type {} ({}) is not MIPS", machine, machine_name(machine))));
+	}
+	let flags = file.u32(36)?;
+	if flags & (EF_MIPS_MICROMIPS | EF_MIPS_ARCH_ASE_M16) != 0 {
+		return Err(MipsError::UnsupportedElf(String::from("microMIPS and MIPS16 code can't be run")));
+	}
+	// MIPS III, IV, V and MIPS64 have 64 bit registers
+	if matches!(flags & EF_MIPS_ARCH, 0x20000000 | 0x30000000 | 0x40000000 | 0x60000000 | 0x80000000 | 0xA0000000) {
+		return Err(MipsError::UnsupportedElf(String::from("64 bit MIPS code can't be run")));
+	}
+	match file.u16(16)? {
+		ET_EXEC => {}
+		ET_REL => { return Err(MipsError::UnsupportedElf(String::from("object files have to be linked into an executable first"))); }
+		ET_DYN => { return Err(MipsError::UnsupportedElf(String::from("only statically linked executables can be loaded"))); }
+		_ => { return Err(MipsError::InvalidImage(String::from("the ELF file is not an executable"))); }
+	}
+	let entry = file.u32(24)?;
+	let (phoff, shoff) = (file.u32(28)? as u64, file.u32(32)? as u64);
+	let (phentsize, phnum) = (file.u16(42)? as u64, file.u16(44)? as u64);
+	let (shentsize, shnum) = (file.u16(46)? as u64, file.u16(48)? as u64);
+	let names = if shnum > 0 { file.u32(shoff + file.u16(50)? as u64 * shentsize + 16)? as u64 } else { 0 };
+
+	let mut segments = vec![];
+	for idx in 0..phnum {
+		let ph = phoff + idx * phentsize;
+		match file.u32(ph)? {
+			PT_LOAD => {}
+			PT_DYNAMIC | PT_INTERP => {
+				return Err(MipsError::UnsupportedElf(String::from("only statically linked executables can be loaded")));
+			}
+			_ => { continue; }
+		}
+		let (offset, address) = (file.u32(ph + 4)?, file.u32(ph + 8)?);
+		let (file_size, mem_size) = (file.u32(ph + 16)?, file.u32(ph + 20)?);
+		let mem_size = mem_size.max(file_size);
+		if address as u64 + mem_size as u64 > 1 << 32 {
+			return Err(MipsError::InvalidImage(format!("the segment at {:#010x} runs past the end of memory", address)));
+		}
+		if mem_size - file_size > MAX_ZERO_FILL {
+			return Err(MipsError::InvalidImage(format!("the segment at {:#010x} is too large", address)));
+		}
+		// whatever the file doesn't hold is zeroed, like .bss
+		let mut contents = file.slice(offset as u64, file_size)?.to_vec();
+		contents.resize(mem_size as usize, 0);
+		segments.push(Segment { address, bytes: contents });
+	}
+
+	let mut symbols = HashMap::new();
+	for idx in 0..shnum {
+		let sh = shoff + idx * shentsize;
+		let kind = file.u32(sh + 4)?;
+		if (kind == SHT_REL || kind == SHT_RELA) && file.u32(sh + 20)? > 0 {
+			let name = file.string(names + file.u32(sh)? as u64)?;
+			return Err(MipsError::UnsupportedElf(format!("the relocations in `{}` can't be applied", name)));
+		}
+		if kind != SHT_SYMTAB {
+			continue;
+		}
+		let (offset, size, entry_size) = (file.u32(sh + 16)? as u64, file.u32(sh + 20)? as u64, file.u32(sh + 36)?);
+		let strtab = file.u32(shoff + file.u32(sh + 24)? as u64 * shentsize + 16)? as u64;
+		for sym in (offset..offset + size).step_by(entry_size.max(1) as usize).skip(1) {
+			let kind = file.slice(sym + 12, 1)?[0] & 0xF;
+			let section = file.u16(sym + 14)?;
+			if kind == STT_SECTION || kind == STT_FILE || section == SHN_UNDEF {
+				continue;
+			}
+			let name = file.string(strtab + file.u32(sym)? as u64)?;
+			if !name.is_empty() {
+				symbols.insert(name, file.u32(sym + 4)?);
+			}
+		}
+	}
+
+	Ok(Image { segments, entry, symbols, endian })
+}
@@ -0,0 +1,129 @@
+// Constant expressions, used by `NAME = value` definitions and anywhere an immediate is read.
+// Precedence follows C, from loosest to tightest:
+//	|
+//	&
+//	<< >>
+//	+ -
+//	* / %
+//	unary - ~ +
+use crate::assembler::{LineContext, resolve_label};
+use crate::lexer::{Token, TokenKind};
+use crate::parser::Expression;
+use crate::mips_int::MipsError;
+
+struct Evaluator<'c> {
+	expr: &'c Expression<'c>,
+	pos: usize,
+	ctx: &'c LineContext<'c>,
+	allow_labels: bool,
+}
+
+/// Evaluates an expression that may use both constants and labels
+pub(crate) fn evaluate(expr: &Expression, ctx: &LineContext) -> Result<i64, MipsError> {
+	Evaluator::new(expr, ctx, true).run()
+}
+
+/// Evaluates an expression that may only use constants.
+/// A name that isn't a constant is reported as an undefined label.
+pub(crate) fn evaluate_constant(expr: &Expression, ctx: &LineContext) -> Result<i64, MipsError> {
+	Evaluator::new(expr, ctx, false).run()
+}
+
+impl<'c> Evaluator<'c> {
+	fn new(expr: &'c Expression<'c>, ctx: &'c LineContext<'c>, allow_labels: bool) -> Evaluator<'c> {
+		Evaluator {
+			expr,
+			pos: 0,
+			ctx,
+			allow_labels,
+		}
+	}
+
+	fn invalid(&self) -> MipsError {
+		MipsError::InvalidExpression(String::from(self.expr.text), self.ctx.line_number)
+	}
+
+	fn run(&mut self) -> Result<i64, MipsError> {
+		let value = self.binary(0)?;
+		if self.pos != self.expr.tokens.len() {
+			return Err(self.invalid());
+		}
+		Ok(value)
+	}
+
+	/// Consumes the next token if it is one of the operators at `level`
+	fn next_operator(&mut self, level: usize) -> Option<&'static str> {
+		const LEVELS: [&[&str]; 5] = [&["|"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+		if let Some(Token { kind: TokenKind::Operator(op), .. }) = self.expr.tokens.get(self.pos) {
+			if LEVELS[level].contains(op) {
+				self.pos += 1;
+				return Some(op);
+			}
+		}
+		None
+	}
+
+	/// Parses a left associative chain of operators at `level` and above
+	fn binary(&mut self, level: usize) -> Result<i64, MipsError> {
+		if level == 5 {
+			return self.unary();
+		}
+		let mut lhs = self.binary(level + 1)?;
+		while let Some(op) = self.next_operator(level) {
+			let rhs = self.binary(level + 1)?;
+			lhs = match op {
+				"|" => { lhs | rhs }
+				"&" => { lhs & rhs }
+				"<<" => { lhs.wrapping_shl(rhs as u32) }
+				">>" => { lhs.wrapping_shr(rhs as u32) }
+				"+" => { lhs.wrapping_add(rhs) }
+				"-" => { lhs.wrapping_sub(rhs) }
+				"*" => { lhs.wrapping_mul(rhs) }
+				"/" => {
+					if rhs == 0 { return Err(self.invalid()); }
+					lhs.wrapping_div(rhs)
+				}
+				"%" => {
+					if rhs == 0 { return Err(self.invalid()); }
+					lhs.wrapping_rem(rhs)
+				}
+				_ => { return Err(self.invalid()); }
+			};
+		}
+		Ok(lhs)
+	}
+
+	fn unary(&mut self) -> Result<i64, MipsError> {
+		let expr = self.expr;
+		let token = expr.tokens.get(self.pos);
+		self.pos += 1;
+		match token.map(|t| &t.kind) {
+			Some(TokenKind::Operator("-")) => { Ok(self.unary()?.wrapping_neg()) }
+			Some(TokenKind::Operator("~")) => { Ok(!self.unary()?) }
+			Some(TokenKind::Operator("+")) => { self.unary() }
+			Some(TokenKind::LeftParen) => {
+				let value = self.binary(0)?;
+				match expr.tokens.get(self.pos) {
+					Some(Token { kind: TokenKind::RightParen, .. }) => {
+						self.pos += 1;
+						Ok(value)
+					}
+					_ => { Err(self.invalid()) }
+				}
+			}
+			Some(TokenKind::Integer(n)) | Some(TokenKind::Char(n)) => { Ok(*n) }
+			Some(TokenKind::Identifier) => { self.lookup(expr.token_text(&expr.tokens[self.pos - 1])) }
+			_ => { Err(self.invalid()) }
+		}
+	}
+
+	fn lookup(&self, name: &str) -> Result<i64, MipsError> {
+		if let Some(v) = self.ctx.variables.get(name) {
+			return Ok(*v as i64);
+		}
+		if !self.allow_labels {
+			return Err(MipsError::UndefinedLabel(String::from(name), self.ctx.line_number));
+		}
+		Ok(resolve_label(name, self.ctx)? as i64)
+	}
+}
@@ -0,0 +1,173 @@
+// An assembled program as it goes into memory, and the file formats it can be
+// carried to other tools in: raw big endian binary, Intel HEX and ELF (see elf.rs).
+//
+// Raw binary has no addresses, so the text and the data go in files of their own
+// and are loaded back at TEXT_BASE and DATA_BASE. Intel HEX keeps the addresses
+// and the entry point, ELF also keeps the labels.
+use std::collections::HashMap;
+
+use crate::memory::{self, Endian};
+use crate::mips_int::MipsError;
+
+/// Bytes that belong at an address
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+	pub address: u32,
+	pub bytes: Vec<u8>,
+}
+
+/// Everything needed to run a program without its source
+#[derive(Debug, Clone)]
+pub struct Image {
+	pub segments: Vec<Segment>,
+	pub entry: u32,						// where execution starts
+	pub symbols: HashMap<String, u32>,	// labels and their addresses
+	pub endian: Endian,					// how the bytes of the segments are ordered
+}
+
+/// Data records in a HEX file hold this many bytes
+const HEX_RECORD_SIZE: u32 = 16;
+
+const HEX_DATA: u8 = 0x00;
+const HEX_END_OF_FILE: u8 = 0x01;
+const HEX_EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const HEX_START_SEGMENT_ADDRESS: u8 = 0x03;
+const HEX_EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const HEX_START_LINEAR_ADDRESS: u8 = 0x05;
+
+impl Image {
+	/// An image from the contents of raw binary files, run from the start of the text
+	pub fn from_raw(text: &[u8], data: &[u8]) -> Result<Image, MipsError> {
+		if text.len() % 4 != 0 {
+			return Err(MipsError::InvalidImage(String::from("the text is not a whole number of instructions")));
+		}
+		let segments = [(memory::TEXT_BASE, text), (memory::DATA_BASE, data)].iter()
+			.filter(|(_, bytes)| !bytes.is_empty())
+			.map(|(address, bytes)| Segment { address: *address, bytes: bytes.to_vec() })
+			.collect();
+		Ok(Image {
+			segments,
+			entry: memory::TEXT_BASE,
+			symbols: HashMap::new(),
+			endian: Endian::Big,
+		})
+	}
+
+	/// The bytes of the segment that starts at `address`, for writing raw binary
+	pub fn segment_bytes(&self, address: u32) -> &[u8] {
+		match self.segments.iter().find(|s| s.address == address) {
+			Some(segment) => { &segment.bytes }
+			None => { &[] }
+		}
+	}
+
+	/// Writes the image as Intel HEX, with 32 bit addresses and the entry point
+	pub fn to_intel_hex(&self) -> String {
+		let mut out = String::new();
+		for segment in self.segments.iter() {
+			let mut upper = None;
+			let mut offset = 0;
+			while offset < segment.bytes.len() {
+				let address = segment.address + offset as u32;
+				if upper != Some(address >> 16) {
+					upper = Some(address >> 16);
+					out.push_str(&hex_record(HEX_EXTENDED_LINEAR_ADDRESS, 0, &((address >> 16) as u16).to_be_bytes()));
+				}
+				// a record can't run past the end of its 64K block
+				let room = 0x10000 - (address & 0xFFFF);
+				let size = HEX_RECORD_SIZE.min(room) as usize;
+				let end = (offset + size).min(segment.bytes.len());
+				out.push_str(&hex_record(HEX_DATA, address as u16, &segment.bytes[offset..end]));
+				offset = end;
+			}
+		}
+		out.push_str(&hex_record(HEX_START_LINEAR_ADDRESS, 0, &self.entry.to_be_bytes()));
+		out.push_str(&hex_record(HEX_END_OF_FILE, 0, &[]));
+		out
+	}
+
+	/// Reads an Intel HEX file. Consecutive bytes are joined back into one segment.
+	/// Without a start address the program runs from the start of the text.
+	pub fn from_intel_hex(text: &str) -> Result<Image, MipsError> {
+		let mut segments: Vec<Segment> = vec![];
+		let mut base = 0;
+		let mut entry = memory::TEXT_BASE;
+		let mut finished = false;
+		for (idx, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() {
+				continue;
+			}
+			let bad = |what: &str| MipsError::InvalidImage(format!("{} on line {}", what, idx + 1));
+			if finished {
+				return Err(bad("record after the end of file"));
+			}
+			let Some(digits) = line.strip_prefix(':') else {
+				return Err(bad("missing ':'"));
+			};
+			let bytes: Option<Vec<u8>> = (0..digits.len())
+				.step_by(2)
+				.map(|i| digits.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+				.collect();
+			let Some(bytes) = bytes else {
+				return Err(bad("invalid hex digits"));
+			};
+			if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+				return Err(bad("wrong record length"));
+			}
+			if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+				return Err(bad("bad checksum"));
+			}
+			let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+			let data = &bytes[4..bytes.len() - 1];
+			let word = |data: &[u8]| data.iter().fold(0u32, |w, b| w << 8 | *b as u32);
+			match bytes[3] {
+				HEX_DATA => {
+					let address = base + address;
+					if address.checked_add((data.len() as u32).saturating_sub(1)).is_none() {
+						return Err(bad("data past the end of memory"));
+					}
+					// nothing can follow data that reaches the top of memory
+					let last_end = match segments.last() {
+						Some(last) => {
+							let end = last.address.checked_add(last.bytes.len() as u32);
+							Some(end.ok_or_else(|| bad("data past the end of memory"))?)
+						}
+						None => { None }
+					};
+					match segments.last_mut() {
+						Some(last) if last_end == Some(address) => { last.bytes.extend_from_slice(data); }
+						_ => { segments.push(Segment { address, bytes: data.to_vec() }); }
+					}
+				}
+				HEX_END_OF_FILE => { finished = true; }
+				HEX_EXTENDED_SEGMENT_ADDRESS if data.len() == 2 => { base = word(data) << 4; }
+				HEX_START_SEGMENT_ADDRESS if data.len() == 4 => {
+					entry = (word(&data[..2]) << 4).wrapping_add(word(&data[2..]));
+				}
+				HEX_EXTENDED_LINEAR_ADDRESS if data.len() == 2 => { base = word(data) << 16; }
+				HEX_START_LINEAR_ADDRESS if data.len() == 4 => { entry = word(data); }
+				kind => { return Err(bad(&format!("unsupported record type {:02X}", kind))); }
+			}
+		}
+		if !finished {
+			return Err(MipsError::InvalidImage(String::from("missing end of file record")));
+		}
+		Ok(Image {
+			segments,
+			entry,
+			symbols: HashMap::new(),
+			endian: Endian::Big,
+		})
+	}
+}
+
+/// One line of Intel HEX: `:`, byte count, address, type, data and checksum
+fn hex_record(kind: u8, address: u16, data: &[u8]) -> String {
+	let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, kind];
+	bytes.extend_from_slice(data);
+	let checksum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg();
+	bytes.push(checksum);
+	let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+	format!(":{}\n", digits)
+}
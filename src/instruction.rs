@@ -38,8 +38,8 @@ pub const OP_LH: u32	= 0b10000100000000000000000000000000;
 pub const OP_LHU: u32	= 0b10010100000000000000000000000000;
 pub const OP_LB: u32	= 0b10000000000000000000000000000000;
 pub const OP_LBU: u32	= 0b10010000000000000000000000000000;
-pub const OP_SW: u32	= 0b10111000000000000000000000000000;
-pub const OP_SH: u32	= 0b10101000000000000000000000000000;
+pub const OP_SW: u32	= 0b10101100000000000000000000000000;
+pub const OP_SH: u32	= 0b10100100000000000000000000000000;
 pub const OP_SB: u32	= 0b10100000000000000000000000000000;
 // branching
 pub const OP_BEQ: u32	= 0b00010000000000000000000000000000;
@@ -49,9 +49,85 @@ pub const OP_J: u32		= 0b00001000000000000000000000000000;
 pub const OP_JAL: u32	= 0b00001100000000000000000000000000;
 pub const OP_SYSCALL: u32 = 0b00000000000000000000000000001100;
 
-// parsing numbers
-pub const OP_FIRST_CODE: u32 = 0b11111100000000000000000000000000;
-pub const OP_SECOND_CODE: u32 = 0b00000000000000000000000000111111;
+/// An instruction word split into the fields of its format.
+///
+/// ```text
+/// R  opcode 0 (6)  rs (5)  rt (5)  rd (5)  shamt (5)  funct (6)
+/// I  opcode (6)    rs (5)  rt (5)  imm (16)
+/// J  opcode (6)    target (26)
+/// ```
+///
+/// Register fields are register numbers, ready to index with.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DecodedInstruction {
+	R { rs: usize, rt: usize, rd: usize, shamt: u32, funct: u32 },
+	I { opcode: u32, rs: usize, rt: usize, imm: u16 },
+	J { opcode: u32, target: u32 },
+}
+
+impl DecodedInstruction {
+	/// Splits a word up by its opcode, the top 6 bits
+	pub fn decode(word: u32) -> DecodedInstruction {
+		let opcode = word >> 26;
+		match opcode {
+			0 => {
+				DecodedInstruction::R {
+					rs: ((word >> 21) & 0x1F) as usize,
+					rt: ((word >> 16) & 0x1F) as usize,
+					rd: ((word >> 11) & 0x1F) as usize,
+					shamt: (word >> 6) & 0x1F,
+					funct: word & 0x3F,
+				}
+			}
+			2 | 3 => { DecodedInstruction::J { opcode, target: word & 0x03FFFFFF } }
+			_ => {
+				DecodedInstruction::I {
+					opcode,
+					rs: ((word >> 21) & 0x1F) as usize,
+					rt: ((word >> 16) & 0x1F) as usize,
+					imm: word as u16,
+				}
+			}
+		}
+	}
+
+	/// Puts the fields back together into a word
+	pub fn encode(&self) -> u32 {
+		match *self {
+			DecodedInstruction::R { rs, rt, rd, shamt, funct } => {
+				(rs as u32 & 0x1F) << 21 | (rt as u32 & 0x1F) << 16 | (rd as u32 & 0x1F) << 11 |
+					(shamt & 0x1F) << 6 | (funct & 0x3F)
+			}
+			DecodedInstruction::I { opcode, rs, rt, imm } => {
+				(opcode & 0x3F) << 26 | (rs as u32 & 0x1F) << 21 | (rt as u32 & 0x1F) << 16 | imm as u32
+			}
+			DecodedInstruction::J { opcode, target } => { (opcode & 0x3F) << 26 | (target & 0x03FFFFFF) }
+		}
+	}
+
+	/// An R format instruction from one of the `OP_*` constants with an opcode of 0
+	pub fn r(op: u32, rs: usize, rt: usize, rd: usize, shamt: u32) -> DecodedInstruction {
+		DecodedInstruction::R { rs, rt, rd, shamt, funct: op & 0x3F }
+	}
+
+	/// An I format instruction from one of the `OP_*` constants
+	pub fn i(op: u32, rs: usize, rt: usize, imm: u16) -> DecodedInstruction {
+		DecodedInstruction::I { opcode: op >> 26, rs, rt, imm }
+	}
+
+	/// A J format instruction from `OP_J` or `OP_JAL`, `target` is the word index
+	pub fn j(op: u32, target: u32) -> DecodedInstruction {
+		DecodedInstruction::J { opcode: op >> 26, target }
+	}
+
+	/// The `OP_*` constant this instruction would match, if it is a real one
+	pub fn op(&self) -> u32 {
+		match *self {
+			DecodedInstruction::R { funct, .. } => { funct }
+			DecodedInstruction::I { opcode, .. } | DecodedInstruction::J { opcode, .. } => { opcode << 26 }
+		}
+	}
+}
 
 #[allow(dead_code)]
 #[derive(Copy, Clone)]
@@ -110,58 +186,112 @@ pub enum OPName {
 }
 
 impl OPName {
-	pub fn from(name: &str) -> u32 {
+	pub fn from(name: &str) -> Option<u32> {
 		match name {
-			"add"	=> { OP_ADD },
-			"addi"	=> { OP_ADDI },
-			"addiu"	=> { OP_ADDIU },
-			"addu"	=> { OP_ADDU },
-			"sub"	=> { OP_SUB },
-			"subu"	=> { OP_SUBU },
-			"and"	=> { OP_AND },
-			"andi"	=> { OP_ANDI },
-			"or"	=> { OP_OR },
-			"ori"	=> { OP_ORI },
-			"xor"	=> { OP_XOR },
-			"xori"	=> { OP_XORI },
-			"nor"	=> { OP_NOR },
-			"sll"	=> { OP_SLL },
-			"srl"	=> { OP_SRL },
-			"sra"	=> { OP_SRA },
-			"sllv"	=> { OP_SLLV },
-			"srlv"	=> { OP_SRLV },
-			"srav"	=> { OP_SRAV },
-			"slt"	=> { OP_SLT },
-			"slti"	=> { OP_SLTI },
-			"sltu"	=> { OP_SLTU },
-			"sltiu" => { OP_SLTIU },
-			"mult"	=> { OP_MULT },
-			"multu" => { OP_MULTU },
-			"div"	=> { OP_DIV },
-			"divu"	=> { OP_DIVU },
-			"mfhi"	=> { OP_MFHI },
-			"mthi"	=> { OP_MTHI },
-			"mflo"	=> { OP_MFLO },
-			"mtlo"	=> { OP_MTLO },
-			"li"	=> { OP_ORI }, // pseudo instruction
-			"lui"	=> { OP_LUI },
+			"add"	=> { Some(OP_ADD) },
+			"addi"	=> { Some(OP_ADDI) },
+			"addiu"	=> { Some(OP_ADDIU) },
+			"addu"	=> { Some(OP_ADDU) },
+			"sub"	=> { Some(OP_SUB) },
+			"subu"	=> { Some(OP_SUBU) },
+			"and"	=> { Some(OP_AND) },
+			"andi"	=> { Some(OP_ANDI) },
+			"or"	=> { Some(OP_OR) },
+			"ori"	=> { Some(OP_ORI) },
+			"xor"	=> { Some(OP_XOR) },
+			"xori"	=> { Some(OP_XORI) },
+			"nor"	=> { Some(OP_NOR) },
+			"sll"	=> { Some(OP_SLL) },
+			"srl"	=> { Some(OP_SRL) },
+			"sra"	=> { Some(OP_SRA) },
+			"sllv"	=> { Some(OP_SLLV) },
+			"srlv"	=> { Some(OP_SRLV) },
+			"srav"	=> { Some(OP_SRAV) },
+			"slt"	=> { Some(OP_SLT) },
+			"slti"	=> { Some(OP_SLTI) },
+			"sltu"	=> { Some(OP_SLTU) },
+			"sltiu" => { Some(OP_SLTIU) },
+			"mult"	=> { Some(OP_MULT) },
+			"multu" => { Some(OP_MULTU) },
+			"div"	=> { Some(OP_DIV) },
+			"divu"	=> { Some(OP_DIVU) },
+			"mfhi"	=> { Some(OP_MFHI) },
+			"mthi"	=> { Some(OP_MTHI) },
+			"mflo"	=> { Some(OP_MFLO) },
+			"mtlo"	=> { Some(OP_MTLO) },
+			"lui"	=> { Some(OP_LUI) },
+			// memory related
+			"lw"	=> { Some(OP_LW) },
+			"lh"	=> { Some(OP_LH) },
+			"lhu"	=> { Some(OP_LHU) },
+			"lb"	=> { Some(OP_LB) },
+			"lbu"	=> { Some(OP_LBU) },
+			"sw"	=> { Some(OP_SW) },
+			"sh"	=> { Some(OP_SH) },
+			"sb"	=> { Some(OP_SB) },
+			// branching
+			"beq"	=> { Some(OP_BEQ) },
+			"bne"	=> { Some(OP_BNE) },
+			"jr"	=> { Some(OP_JR) },
+			"j"		=> { Some(OP_J) },
+			"jal"	=> { Some(OP_JAL) },
+			"syscall" => { Some(OP_SYSCALL) },
+			&_ => { None }
+		}
+	}
+
+	/// The mnemonic of one of the `OP_*` constants, the reverse of `from`
+	pub fn name(op: u32) -> Option<&'static str> {
+		match op {
+			OP_ADD	=> { Some("add") },
+			OP_ADDI	=> { Some("addi") },
+			OP_ADDIU	=> { Some("addiu") },
+			OP_ADDU	=> { Some("addu") },
+			OP_SUB	=> { Some("sub") },
+			OP_SUBU	=> { Some("subu") },
+			OP_AND	=> { Some("and") },
+			OP_ANDI	=> { Some("andi") },
+			OP_OR	=> { Some("or") },
+			OP_ORI	=> { Some("ori") },
+			OP_XOR	=> { Some("xor") },
+			OP_XORI	=> { Some("xori") },
+			OP_NOR	=> { Some("nor") },
+			OP_SLL	=> { Some("sll") },
+			OP_SRL	=> { Some("srl") },
+			OP_SRA	=> { Some("sra") },
+			OP_SLLV	=> { Some("sllv") },
+			OP_SRLV	=> { Some("srlv") },
+			OP_SRAV	=> { Some("srav") },
+			OP_SLT	=> { Some("slt") },
+			OP_SLTI	=> { Some("slti") },
+			OP_SLTU	=> { Some("sltu") },
+			OP_SLTIU	=> { Some("sltiu") },
+			OP_MULT	=> { Some("mult") },
+			OP_MULTU	=> { Some("multu") },
+			OP_DIV	=> { Some("div") },
+			OP_DIVU	=> { Some("divu") },
+			OP_MFHI	=> { Some("mfhi") },
+			OP_MTHI	=> { Some("mthi") },
+			OP_MFLO	=> { Some("mflo") },
+			OP_MTLO	=> { Some("mtlo") },
+			OP_LUI	=> { Some("lui") },
 			// memory related
-			"lw"	=> { OP_LW },
-			"lh"	=> { OP_LH },
-			"lhu"	=> { OP_LHU },
-			"lb"	=> { OP_LB },
-			"lbu"	=> { OP_LBU },
-			"sw"	=> { OP_SW },
-			"sh"	=> { OP_SH },
-			"sb"	=> { OP_SB },
+			OP_LW	=> { Some("lw") },
+			OP_LH	=> { Some("lh") },
+			OP_LHU	=> { Some("lhu") },
+			OP_LB	=> { Some("lb") },
+			OP_LBU	=> { Some("lbu") },
+			OP_SW	=> { Some("sw") },
+			OP_SH	=> { Some("sh") },
+			OP_SB	=> { Some("sb") },
 			// branching
-			"beq"	=> { OP_BEQ },
-			"bne"	=> { OP_BNE },
-			"jr"	=> { OP_JR },
-			"j"		=> { OP_J },
-			"jal"	=> { OP_JAL },
-			"syscall" => { OP_SYSCALL },
-			&_ => { 0 }
+			OP_BEQ	=> { Some("beq") },
+			OP_BNE	=> { Some("bne") },
+			OP_JR	=> { Some("jr") },
+			OP_J	=> { Some("j") },
+			OP_JAL	=> { Some("jal") },
+			OP_SYSCALL	=> { Some("syscall") },
+			_ => { None }
 		}
 	}
 }
@@ -0,0 +1,257 @@
+// Splits one line of assembly into tokens. Whitespace only separates tokens,
+// and a '#' outside of a string or character starts a comment that runs to the
+// end of the line, so nothing after it is returned.
+//
+//	loop:	lw $t1, -4($sp)		# load
+//	Identifier Colon Identifier Register Comma Operator Integer LeftParen Register RightParen
+//
+// Every token knows where it sits in the line, so the parser can hand back
+// the text an operand was written as.
+use crate::mips_int::MipsError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TokenKind {
+	Identifier,				// mnemonics, directives, labels and constants
+	Register,				// `$t0`, `$8`, checked when it is used as one
+	Integer(i64),
+	Float(f64),				// only ever in `.float` and `.double`
+	String(Vec<u8>),		// with the escapes already read
+	Char(i64),				// `'a'`, which is just a number
+	Comma,
+	Colon,
+	Equals,
+	LeftParen,
+	RightParen,
+	Operator(&'static str),	// + - * / % & | ~ << >>
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Token {
+	pub(crate) kind: TokenKind,
+	pub(crate) start: usize,	// byte offsets into the line
+	pub(crate) end: usize,
+}
+
+/// Reads a number written in decimal, hex (`0x..`) or binary (`0b..`),
+/// with an optional leading '-'
+pub(crate) fn parse_number(text: &str) -> Option<i64> {
+	let (negative, digits) = match text.strip_prefix('-') {
+		Some(rest) => (true, rest),
+		None => (false, text),
+	};
+	let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+		i64::from_str_radix(hex, 16).ok()?
+	} else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+		i64::from_str_radix(bin, 2).ok()?
+	} else {
+		digits.parse::<i64>().ok()?
+	};
+	Some(if negative { -value } else { value })
+}
+
+fn is_name_start(c: char) -> bool {
+	c.is_alphabetic() || c == '_' || c == '.'
+}
+
+fn is_name_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+struct Lexer<'s> {
+	text: &'s str,
+	chars: Vec<(usize, char)>,
+	pos: usize,
+	line_number: usize,
+}
+
+/// Splits a line into tokens, leaving out whitespace and the comment
+pub(crate) fn tokenize(text: &str, line_number: usize) -> Result<Vec<Token>, MipsError> {
+	let mut lexer = Lexer {
+		text,
+		chars: text.char_indices().collect(),
+		pos: 0,
+		line_number,
+	};
+	let mut tokens = vec![];
+	while let Some(c) = lexer.peek() {
+		if c == '#' {
+			break;
+		}
+		if c.is_whitespace() {
+			lexer.pos += 1;
+			continue;
+		}
+		let start = lexer.offset();
+		let kind = lexer.token(c)?;
+		tokens.push(Token { kind, start, end: lexer.offset() });
+	}
+	Ok(tokens)
+}
+
+impl<'s> Lexer<'s> {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).map(|(_, c)| *c)
+	}
+
+	fn peek_at(&self, ahead: usize) -> Option<char> {
+		self.chars.get(self.pos + ahead).map(|(_, c)| *c)
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek();
+		self.pos += 1;
+		c
+	}
+
+	/// Byte offset of the next character
+	fn offset(&self) -> usize {
+		self.chars.get(self.pos).map_or(self.text.len(), |(i, _)| *i)
+	}
+
+	fn skip_while(&mut self, keep: fn(char) -> bool) {
+		while self.peek().is_some_and(keep) {
+			self.pos += 1;
+		}
+	}
+
+	fn syntax_error(&self) -> MipsError {
+		MipsError::SyntaxError(self.line_number)
+	}
+
+	fn token(&mut self, c: char) -> Result<TokenKind, MipsError> {
+		if c.is_ascii_digit() {
+			return self.number();
+		}
+		if is_name_start(c) {
+			self.skip_while(is_name_char);
+			return Ok(TokenKind::Identifier);
+		}
+		self.pos += 1;
+		let kind = match c {
+			'$' => {
+				self.skip_while(char::is_alphanumeric);
+				TokenKind::Register
+			}
+			'"' => { TokenKind::String(self.string()?) }
+			'\'' => {
+				let value = match self.next() {
+					Some('\\') => { self.escape()? as i64 }
+					Some(c) if c != '\'' => { c as i64 }
+					_ => { return Err(self.syntax_error()); }
+				};
+				if self.next() != Some('\'') {
+					return Err(self.syntax_error());
+				}
+				TokenKind::Char(value)
+			}
+			',' => { TokenKind::Comma }
+			':' => { TokenKind::Colon }
+			'=' => { TokenKind::Equals }
+			'(' => { TokenKind::LeftParen }
+			')' => { TokenKind::RightParen }
+			'<' | '>' => {
+				if self.next() != Some(c) {
+					return Err(self.syntax_error());
+				}
+				TokenKind::Operator(if c == '<' { "<<" } else { ">>" })
+			}
+			'+' => { TokenKind::Operator("+") }
+			'-' => { TokenKind::Operator("-") }
+			'*' => { TokenKind::Operator("*") }
+			'/' => { TokenKind::Operator("/") }
+			'%' => { TokenKind::Operator("%") }
+			'&' => { TokenKind::Operator("&") }
+			'|' => { TokenKind::Operator("|") }
+			'~' => { TokenKind::Operator("~") }
+			_ => { return Err(self.syntax_error()); }
+		};
+		Ok(kind)
+	}
+
+	/// An integer in decimal, hex (`0x..`) or binary (`0b..`),
+	/// or a decimal float like `1.5` or `2e10`
+	fn number(&mut self) -> Result<TokenKind, MipsError> {
+		let start = self.offset();
+		self.skip_while(|c| c.is_ascii_digit());
+		let fraction = self.peek() == Some('.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit());
+		if fraction {
+			self.pos += 1;
+			self.skip_while(|c| c.is_ascii_digit());
+		}
+		let exponent = matches!(self.peek(), Some('e') | Some('E')) && match self.peek_at(1) {
+			Some('+') | Some('-') => { self.peek_at(2).is_some_and(|c| c.is_ascii_digit()) }
+			Some(c) => { c.is_ascii_digit() }
+			None => { false }
+		};
+		if exponent {
+			self.pos += 2;
+			self.skip_while(|c| c.is_ascii_digit());
+		}
+		if fraction || exponent {
+			let text = &self.text[start..self.offset()];
+			return match text.parse::<f64>() {
+				Ok(value) => { Ok(TokenKind::Float(value)) }
+				Err(_) => { Err(MipsError::InvalidExpression(String::from(text), self.line_number)) }
+			};
+		}
+		// hex and binary digits are letters too, parse_number sorts them out
+		self.skip_while(|c| c.is_ascii_alphanumeric() || c == '_');
+		let text = &self.text[start..self.offset()];
+		match parse_number(text) {
+			Some(value) => { Ok(TokenKind::Integer(value)) }
+			None => { Err(MipsError::InvalidExpression(String::from(text), self.line_number)) }
+		}
+	}
+
+	/// Reads a double quoted string, after the opening quote, into the bytes it stands for.
+	/// Anything outside of ASCII is stored as UTF-8.
+	fn string(&mut self) -> Result<Vec<u8>, MipsError> {
+		let mut bytes = vec![];
+		loop {
+			match self.next() {
+				Some('"') => { return Ok(bytes); }
+				Some('\\') => { bytes.push(self.escape()?); }
+				Some(c) => {
+					let mut buf = [0; 4];
+					bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+				}
+				None => { return Err(self.syntax_error()); }
+			}
+		}
+	}
+
+	/// Reads the escape after a '\'. Understands `\n \t \r \0 \\ \" \'`,
+	/// octal `\101` and hex `\x41`.
+	fn escape(&mut self) -> Result<u8, MipsError> {
+		let Some(escape) = self.next() else {
+			return Err(self.syntax_error());
+		};
+		match escape {
+			'n' => { Ok(b'\n') }
+			't' => { Ok(b'\t') }
+			'r' => { Ok(b'\r') }
+			'\\' | '"' | '\'' => { Ok(escape as u8) }
+			'0'..='7' => {
+				// up to three octal digits, \0 on its own is the NULL character
+				let mut value = escape.to_digit(8).unwrap_or(0);
+				for _ in 0..2 {
+					let Some(digit) = self.peek().and_then(|d| d.to_digit(8)) else { break; };
+					value = value * 8 + digit;
+					self.pos += 1;
+				}
+				u8::try_from(value).map_err(|_| self.syntax_error())
+			}
+			'x' => {
+				let Some(mut value) = self.next().and_then(|d| d.to_digit(16)) else {
+					return Err(self.syntax_error());
+				};
+				if let Some(digit) = self.peek().and_then(|d| d.to_digit(16)) {
+					value = value * 16 + digit;
+					self.pos += 1;
+				}
+				Ok(value as u8)
+			}
+			_ => { Err(self.syntax_error()) }
+		}
+	}
+}
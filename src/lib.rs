@@ -1,9 +1,22 @@
 #![feature(let_else)]
 
 pub mod mips_int;
+pub mod memory;
 pub mod mips_int_gui;
 
 #[cfg(test)]
 mod unit_tests;
 pub mod register;
 mod instruction;
+mod pseudo;
+mod expression;
+mod lexer;
+mod parser;
+mod preprocessor;
+mod listing;
+pub mod source_map;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod image;
+mod elf;
+pub mod assembler;
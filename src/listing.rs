@@ -0,0 +1,197 @@
+// A listing shows what the assembler did with every line of the source:
+// where it landed, the machine code it became, and the real instruction
+// for anything that was a pseudo instruction.
+//
+//	Segment  Address     Code          Line  Basic                         Source
+//	.text    0x00400004  0x3c011234       6  lui $at, 4660                 li $t0, 0x12345678
+//	.text    0x00400008  0x34285678       6  ori $t0, $at, 22136           li $t0, 0x12345678
+//
+// The symbol table and a cross reference of where each label is used come at the end.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::memory::{self, Memory};
+use crate::assembler::strip_comment;
+use crate::preprocessor::SourceLine;
+
+/// Data lines longer than this many rows, like a big `.space`, are cut short
+const MAX_DATA_ROWS: u32 = 4;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Segment {
+	Data,
+	Text,
+}
+
+/// One row of the listing, for a line that placed something in memory
+#[derive(Clone)]
+pub struct ListingEntry {
+	pub segment: Segment,
+	pub address: u32,
+	pub size: u32,					// bytes placed
+	pub basic: Option<String>,		// the real instruction, only for code
+	pub source: String,				// the line as it was written
+	pub file: Option<Rc<PathBuf>>,
+	pub line_number: usize,
+}
+
+/// Everything the assembler writes down while encoding, to be turned into a listing later
+#[derive(Clone)]
+pub struct Listing {
+	entries: Vec<ListingEntry>,
+	definitions: HashMap<String, (Option<Rc<PathBuf>>, usize)>,	// where each label was written
+}
+
+impl Segment {
+	pub fn name(&self) -> &'static str {
+		match self {
+			Segment::Data => { ".data" }
+			Segment::Text => { ".text" }
+		}
+	}
+}
+
+/// `file:line`, or just the line when the source wasn't read from a file
+fn location(file: &Option<Rc<PathBuf>>, line_number: usize) -> String {
+	match file.as_ref().and_then(|f| f.file_name()) {
+		Some(name) => { format!("{}:{}", name.to_string_lossy(), line_number) }
+		None => { line_number.to_string() }
+	}
+}
+
+fn is_name_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+/// Every name used in a line, leaving out strings and label definitions
+fn names_in(text: &str) -> Vec<String> {
+	let chars: Vec<char> = strip_comment(text).chars().collect();
+	let mut names = vec![];
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c == '"' || c == '\'' {
+			i += 1;
+			while i < chars.len() && chars[i] != c {
+				if chars[i] == '\\' {
+					i += 1;
+				}
+				i += 1;
+			}
+			i += 1;
+		} else if is_name_char(c) {
+			let start = i;
+			while i < chars.len() && is_name_char(chars[i]) {
+				i += 1;
+			}
+			if chars.get(i) != Some(&':') {
+				names.push(chars[start..i].iter().collect());
+			}
+		} else {
+			i += 1;
+		}
+	}
+	names
+}
+
+impl Listing {
+	pub fn new() -> Listing {
+		Listing {
+			entries: vec![],
+			definitions: HashMap::new(),
+		}
+	}
+
+	pub fn add(&mut self, entry: ListingEntry) {
+		self.entries.push(entry);
+	}
+
+	/// Remembers the line a label was written on, for the cross reference
+	pub fn define(&mut self, label: &str, source: &SourceLine) {
+		self.definitions.insert(String::from(label), (source.file.clone(), source.line_number));
+	}
+
+	/// Renders the listing as text.
+	///
+	/// # Arguments
+	///
+	/// * `memory` - The assembled program the entries point into
+	/// * `labels` - Every label and its byte address
+	pub fn render(&self, memory: &Memory, labels: &HashMap<String, u32>) -> String {
+		let mut out = String::new();
+		let _ = writeln!(out, "Segment  Address     Code          Line  Basic                         Source");
+		for entry in self.entries.iter() {
+			if let Some(basic) = &entry.basic {
+				let word = memory.read_word(entry.address).unwrap_or(0);
+				let _ = writeln!(out, "{:<8} {:#010x}  {:<12}  {:>4}  {:<29} {}",
+								 entry.segment.name(), entry.address, format!("{:#010x}", word),
+								 entry.line_number, basic, entry.source);
+				continue;
+			}
+
+			// data is shown a word's worth of bytes at a time
+			let end = entry.address + entry.size;
+			for (row, start) in (entry.address..end).step_by(4).enumerate() {
+				if row as u32 == MAX_DATA_ROWS {
+					let _ = writeln!(out, "                     ...");
+					break;
+				}
+				let bytes: Vec<String> = (start..end.min(start + 4))
+					.map(|a| format!("{:02x}", memory.read_byte(a).unwrap_or(0)))
+					.collect();
+				if row == 0 {
+					let _ = writeln!(out, "{:<8} {:#010x}  {:<12}  {:>4}  {:<29} {}",
+									 entry.segment.name(), start, bytes.join(" "),
+									 entry.line_number, "", entry.source);
+				} else {
+					let _ = writeln!(out, "         {:#010x}  {}", start, bytes.join(" "));
+				}
+			}
+		}
+
+		let mut symbols: Vec<(&String, &u32)> = labels.iter().collect();
+		symbols.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+
+		let _ = writeln!(out, "\nSymbol table");
+		let _ = writeln!(out, "Name                     Segment  Address");
+		for (name, addr) in symbols.iter() {
+			let segment = if **addr >= memory::DATA_BASE { Segment::Data } else { Segment::Text };
+			let _ = writeln!(out, "{:<24} {:<8} {:#010x}", name, segment.name(), addr);
+		}
+
+		// every line that mentions a label, once per source line even when
+		// a pseudo instruction turned it into several rows
+		let mut uses: HashMap<&str, Vec<String>> = HashMap::new();
+		let mut last = None;
+		for entry in self.entries.iter() {
+			let here = location(&entry.file, entry.line_number);
+			if last.as_ref() == Some(&here) {
+				continue;
+			}
+			for name in names_in(&entry.source) {
+				if let Some((label, _)) = labels.get_key_value(&name) {
+					let lines = uses.entry(label.as_str()).or_default();
+					if !lines.contains(&here) {
+						lines.push(here.clone());
+					}
+				}
+			}
+			last = Some(here);
+		}
+
+		let _ = writeln!(out, "\nCross reference");
+		let _ = writeln!(out, "Name                     Defined          Used");
+		for (name, _) in symbols.iter() {
+			let defined = match self.definitions.get(name.as_str()) {
+				Some((file, line)) => { location(file, *line) }
+				None => { String::from("-") }
+			};
+			let used = uses.get(name.as_str()).map(|l| l.join(", ")).unwrap_or_default();
+			let row = format!("{:<24} {:<16} {}", name, defined, used);
+			let _ = writeln!(out, "{}", row.trim_end());
+		}
+		out
+	}
+}
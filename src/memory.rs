@@ -0,0 +1,295 @@
+// The address space a program runs in, laid out the way MARS does it:
+//
+//	0x7fffeffc	$sp starts here, the stack grows down
+//	    ...
+//	          	the heap grows up from the end of the static data
+//	0x10010000	.data
+//	0x10008000	$gp
+//	0x00400000	.text
+//
+// Memory is big endian, like the assembler writes it, unless a little endian
+// program was loaded. Programs loaded from a file can also place segments anywhere else.
+use crate::image::Segment;
+use crate::mips_int::MipsError;
+
+pub const TEXT_BASE: u32 = 0x00400000;
+pub const DATA_BASE: u32 = 0x10010000;
+pub const GLOBAL_POINTER: u32 = 0x10008000;
+/// The static data has to end by here, where MARS starts the heap
+pub const DATA_LIMIT: u32 = 0x10040000;
+pub const STACK_POINTER: u32 = 0x7fffeffc;
+/// The stack can grow down to here
+pub const STACK_LIMIT: u32 = 0x7f800000;
+/// One past the last byte of the stack
+const STACK_END: u32 = 0x80000000;
+/// The stack grows this many bytes at a time
+const STACK_CHUNK: u32 = 4096;
+
+/// The order of the bytes in a halfword or word
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Endian {
+	Big,
+	Little,
+}
+
+pub struct Memory {
+	text: Vec<u32>,		// instructions from TEXT_BASE
+	data: Vec<u8>,		// static data from DATA_BASE, then the heap
+	static_size: u32,	// bytes of data written by the assembler
+	stack: Vec<u8>,		// the bytes just below STACK_END, lowest address first
+	regions: Vec<Segment>,	// loaded segments outside of the usual places
+	endian: Endian,
+}
+
+/// Where the byte at an address lives
+enum Location {
+	Text(usize, u32),	// word index and byte within it
+	Data(usize),
+	Stack(u32),			// bytes below STACK_END
+	Region(usize, usize),	// which region and the byte within it
+}
+
+impl Default for Memory {
+	fn default() -> Memory {
+		Memory::new()
+	}
+}
+
+impl Memory {
+	pub fn new() -> Memory {
+		Memory {
+			text: vec![],
+			data: vec![],
+			static_size: 0,
+			stack: vec![],
+			regions: vec![],
+			endian: Endian::Big,
+		}
+	}
+
+	pub fn endian(&self) -> Endian {
+		self.endian
+	}
+
+	/// Changes the byte order, before anything is mapped
+	pub fn set_endian(&mut self, endian: Endian) {
+		self.endian = endian;
+	}
+
+	pub fn text(&self) -> &[u32] {
+		&self.text
+	}
+
+	/// The static data, followed by whatever the heap has grown to
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Appends an instruction to the text segment, returning its address
+	pub fn push_text(&mut self, word: u32) -> u32 {
+		self.text.push(word);
+		TEXT_BASE + 4 * (self.text.len() as u32 - 1)
+	}
+
+	/// Appends a byte to the static data, returning its address
+	pub fn push_data(&mut self, byte: u8) -> u32 {
+		self.data.push(byte);
+		self.static_size = self.data.len() as u32;
+		DATA_BASE + self.static_size - 1
+	}
+
+	/// Places a segment that was loaded from a file.
+	/// Code at `TEXT_BASE` and data at `DATA_BASE` go where the assembler would have
+	/// put them, anything else becomes a region of its own.
+	pub fn map(&mut self, segment: Segment) -> Result<(), MipsError> {
+		let size = segment.bytes.len() as u32;
+		if size == 0 {
+			return Ok(());
+		}
+		// the text is kept as words, which only line up with big endian bytes
+		if segment.address == TEXT_BASE && self.text.is_empty() && size % 4 == 0 && self.endian == Endian::Big {
+			self.text = segment.bytes.chunks(4)
+				.map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+				.collect();
+			return Ok(());
+		}
+		if segment.address == DATA_BASE && self.data.is_empty() {
+			self.data = segment.bytes;
+			self.static_size = size;
+			return Ok(());
+		}
+		let Some(last) = segment.address.checked_add(size - 1) else {
+			return Err(MipsError::AddressOutOfRange(segment.address));
+		};
+		for addr in [segment.address, last] {
+			if self.locate(addr).is_some() {
+				return Err(MipsError::AddressOutOfRange(addr));
+			}
+		}
+		self.regions.push(segment);
+		Ok(())
+	}
+
+	/// Everything that was assembled or loaded, the text first
+	pub fn segments(&self) -> Vec<Segment> {
+		let mut segments = vec![];
+		if !self.text.is_empty() {
+			segments.push(Segment {
+				address: TEXT_BASE,
+				bytes: self.text.iter().flat_map(|w| w.to_be_bytes()).collect(),
+			});
+		}
+		if self.static_size > 0 {
+			segments.push(Segment {
+				address: DATA_BASE,
+				bytes: self.data[..self.static_size as usize].to_vec(),
+			});
+		}
+		segments.extend(self.regions.iter().cloned());
+		segments
+	}
+
+	pub fn static_size(&self) -> u32 {
+		self.static_size
+	}
+
+	/// The first address of the heap, the word after the static data
+	pub fn heap_start(&self) -> u32 {
+		DATA_BASE + ((self.static_size + 3) & !3)
+	}
+
+	/// One past the last address of the heap
+	pub fn heap_end(&self) -> u32 {
+		DATA_BASE + (self.data.len() as u32).max(self.heap_start() - DATA_BASE)
+	}
+
+	/// Grows the heap by `bytes`, returning the address of the new space
+	pub fn allocate(&mut self, bytes: u32) -> Result<u32, MipsError> {
+		let start = self.heap_end();
+		let Some(end) = start.checked_add(bytes).filter(|end| *end <= STACK_LIMIT) else {
+			return Err(MipsError::AddressOutOfRange(start));
+		};
+		self.data.resize((end - DATA_BASE) as usize, 0);
+		Ok(start)
+	}
+
+	fn locate(&self, addr: u32) -> Option<Location> {
+		if addr >= TEXT_BASE && ((addr - TEXT_BASE) / 4) < self.text.len() as u32 {
+			let offset = addr - TEXT_BASE;
+			Some(Location::Text((offset / 4) as usize, offset % 4))
+		} else if addr >= DATA_BASE && addr < self.heap_end() {
+			Some(Location::Data((addr - DATA_BASE) as usize))
+		} else if (STACK_LIMIT..STACK_END).contains(&addr) {
+			Some(Location::Stack(STACK_END - addr))
+		} else {
+			self.regions.iter().enumerate()
+				.find(|(_, r)| addr >= r.address && ((addr - r.address) as usize) < r.bytes.len())
+				.map(|(idx, r)| Location::Region(idx, (addr - r.address) as usize))
+		}
+	}
+
+	pub fn read_byte(&self, addr: u32) -> Result<u8, MipsError> {
+		match self.locate(addr) {
+			Some(Location::Text(idx, byte)) => { Ok((self.text[idx] >> (24 - 8 * byte)) as u8) }
+			// the static data is padded out to a whole word
+			Some(Location::Data(idx)) => { Ok(self.data.get(idx).copied().unwrap_or(0)) }
+			Some(Location::Region(idx, offset)) => { Ok(self.regions[idx].bytes[offset]) }
+			Some(Location::Stack(below)) => {
+				// the stack reads as zeros below what has been written so far
+				let len = self.stack.len() as u32;
+				Ok(if below <= len { self.stack[(len - below) as usize] } else { 0 })
+			}
+			None => { Err(MipsError::AddressOutOfRange(addr)) }
+		}
+	}
+
+	pub fn write_byte(&mut self, addr: u32, value: u8) -> Result<(), MipsError> {
+		match self.locate(addr) {
+			Some(Location::Data(idx)) => {
+				if idx >= self.data.len() {
+					self.data.resize(idx + 1, 0);
+				}
+				self.data[idx] = value;
+			}
+			Some(Location::Region(idx, offset)) => { self.regions[idx].bytes[offset] = value; }
+			Some(Location::Stack(below)) => {
+				let len = self.stack.len() as u32;
+				if below > len {
+					// grow down a chunk at a time, without passing the limit
+					let new_len = (below.div_ceil(STACK_CHUNK) * STACK_CHUNK).min(STACK_END - STACK_LIMIT);
+					let mut grown = vec![0; (new_len - len) as usize];
+					grown.extend_from_slice(&self.stack);
+					self.stack = grown;
+				}
+				let len = self.stack.len() as u32;
+				self.stack[(len - below) as usize] = value;
+			}
+			// the text segment is read only
+			Some(Location::Text(_, _)) | None => { return Err(MipsError::AddressOutOfRange(addr)); }
+		}
+		Ok(())
+	}
+
+	pub fn read_half(&self, addr: u32) -> Result<u16, MipsError> {
+		if addr % 2 != 0 {
+			return Err(MipsError::UnalignedAddress(addr));
+		}
+		let bytes = [self.read_byte(addr)?, self.read_byte(addr + 1)?];
+		Ok(match self.endian {
+			Endian::Big => { u16::from_be_bytes(bytes) }
+			Endian::Little => { u16::from_le_bytes(bytes) }
+		})
+	}
+
+	pub fn read_word(&self, addr: u32) -> Result<u32, MipsError> {
+		if addr % 4 != 0 {
+			return Err(MipsError::UnalignedAddress(addr));
+		}
+		if let Some(Location::Text(idx, _)) = self.locate(addr) {
+			return Ok(self.text[idx]);
+		}
+		let (first, second) = (self.read_half(addr)? as u32, self.read_half(addr + 2)? as u32);
+		Ok(match self.endian {
+			Endian::Big => { first << 16 | second }
+			Endian::Little => { second << 16 | first }
+		})
+	}
+
+	/// Reads the instruction at `pc`.
+	/// Only the text segment and segments loaded from a file can be run,
+	/// not the data, the heap or the stack.
+	pub fn fetch(&self, pc: u32) -> Result<u32, MipsError> {
+		if pc % 4 != 0 {
+			return Err(MipsError::UnalignedPc(pc));
+		}
+		match self.locate(pc) {
+			Some(Location::Text(idx, _)) => { Ok(self.text[idx]) }
+			Some(Location::Region(_, _)) => { self.read_word(pc) }
+			_ => { Err(MipsError::PcOutOfText(pc)) }
+		}
+	}
+
+	pub fn write_half(&mut self, addr: u32, value: u16) -> Result<(), MipsError> {
+		if addr % 2 != 0 {
+			return Err(MipsError::UnalignedAddress(addr));
+		}
+		let bytes = match self.endian {
+			Endian::Big => { value.to_be_bytes() }
+			Endian::Little => { value.to_le_bytes() }
+		};
+		self.write_byte(addr, bytes[0])?;
+		self.write_byte(addr + 1, bytes[1])
+	}
+
+	pub fn write_word(&mut self, addr: u32, value: u32) -> Result<(), MipsError> {
+		if addr % 4 != 0 {
+			return Err(MipsError::UnalignedAddress(addr));
+		}
+		let (first, second) = match self.endian {
+			Endian::Big => { (value >> 16, value) }
+			Endian::Little => { (value, value >> 16) }
+		};
+		self.write_half(addr, first as u16)?;
+		self.write_half(addr + 2, second as u16)
+	}
+}
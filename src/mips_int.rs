@@ -1,19 +1,25 @@
-use std::collections::btree_map::Range;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
 use std::fs;
-use std::ops::Add;
-use byteorder::{BigEndian, ReadBytesExt};
+use std::iter;
 
 use crate::register::RegNames;
 use crate::register::Register;
-use regex::{Regex, Split};
-use crate::{instruction, register};
-use crate::instruction::OPName;
+use crate::instruction;
+use crate::instruction::DecodedInstruction;
+use crate::listing::Listing;
+use crate::source_map::{SourceLocation, SourceMap};
+use crate::diagnostic::Diagnostic;
+use crate::memory::{self, Memory};
+use crate::disassembler::Disassembler;
+use crate::image::Image;
+use crate::elf;
+use crate::assembler::{Assembler, Program};
 
 //https://www.cs.unibo.it/~solmi/teaching/arch_2002-2003/AssemblyLanguageProgDoc.pdf
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MipsError {
 	UnknownInstruction(u32),
 	SyntaxError(usize),
@@ -21,82 +27,177 @@ pub enum MipsError {
 	InvalidMain,
 	UnalignedBytes,
 	FileNotFound,
+	UndefinedLabel(String, usize),
+	BranchOutOfRange(String, usize),
+	ImmediateOutOfRange(i64, usize),
+	UnknownMnemonic(String, usize),
+	InvalidExpression(String, usize),
+	InMacro(String, usize, Box<MipsError>),	// macro name, line of the call, error in the body
+	InFile(String, Box<MipsError>),				// file name, error on a line of that file
+	IncludeNotFound(String, usize),
+	IncludeCycle(String, usize),
+	FileNotWritten(String),
+	AtAddress(u32, usize, Box<MipsError>),		// address and source line of a failing instruction
+	UnknownRegister(String, usize),
+	ReservedRegister(String, usize),			// only ever a warning
+	DuplicateLabel(String, usize),
+	InstructionInData(String, usize),
+	AddressOutOfRange(u32),
+	UnalignedAddress(u32),
+	UnalignedPc(u32),
+	PcOutOfText(u32),							// the PC ran off the end of the code, or jumped out of it
+	InvalidImage(String),
+	UnsupportedElf(String),
+	UnknownSyscall(u32),
+	ArithmeticOverflow,							// add, sub or addi overflowed as signed numbers
+	InvalidInput(String),						// what was typed when a number was asked for
+	Diagnostics(Vec<Diagnostic>),				// everything found while assembling
 }
 
-enum LoadingState {
-	FileOpen,
-	Data,
-	Code
+impl MipsError {
+	/// What went wrong, without saying where
+	pub fn message(&self) -> String {
+		match self {
+			MipsError::UnknownInstruction(word) => { format!("unknown instruction word {:#010x}", word) }
+			MipsError::SyntaxError(_) => { String::from("syntax error") }
+			MipsError::MissingMain => { String::from("missing `main` label") }
+			MipsError::InvalidMain => { String::from("`main` is not word aligned") }
+			MipsError::UnalignedBytes => { String::from("unaligned bytes") }
+			MipsError::FileNotFound => { String::from("program file unable to be opened") }
+			MipsError::UndefinedLabel(lbl, _) => { format!("undefined label `{}`", lbl) }
+			MipsError::BranchOutOfRange(lbl, _) => { format!("label `{}` is out of range", lbl) }
+			MipsError::ImmediateOutOfRange(v, _) => { format!("immediate `{}` is out of range", v) }
+			MipsError::UnknownMnemonic(op, _) => { format!("unknown instruction `{}`", op) }
+			MipsError::InvalidExpression(e, _) => { format!("invalid expression `{}`", e) }
+			MipsError::InMacro(_, _, inner) => { inner.message() }
+			MipsError::InFile(_, inner) => { inner.message() }
+			MipsError::IncludeNotFound(file, _) => { format!("unable to include `{}`", file) }
+			MipsError::IncludeCycle(file, _) => { format!("`{}` includes itself", file) }
+			MipsError::FileNotWritten(file) => { format!("unable to write `{}`", file) }
+			MipsError::AtAddress(_, _, inner) => { inner.message() }
+			MipsError::UnknownRegister(reg, _) => { format!("unknown register `{}`", reg) }
+			MipsError::ReservedRegister(reg, _) => { format!("`{}` is reserved for the assembler", reg) }
+			MipsError::DuplicateLabel(lbl, _) => { format!("label `{}` is defined more than once", lbl) }
+			MipsError::InstructionInData(op, _) => { format!("instruction `{}` is not allowed in the data segment", op) }
+			MipsError::AddressOutOfRange(addr) => { format!("address {:#010x} is outside of memory", addr) }
+			MipsError::UnalignedAddress(addr) => { format!("address {:#010x} is not aligned", addr) }
+			MipsError::UnalignedPc(pc) => { format!("the PC {:#010x} is not word aligned", pc) }
+			MipsError::PcOutOfText(pc) => { format!("the PC {:#010x} is outside of the text segment", pc) }
+			MipsError::InvalidImage(what) => { format!("unable to load program image: {}", what) }
+			MipsError::UnsupportedElf(what) => { format!("unsupported ELF file: {}", what) }
+			MipsError::UnknownSyscall(service) => { format!("unknown syscall {}", service) }
+			MipsError::ArithmeticOverflow => { String::from("arithmetic overflow") }
+			MipsError::InvalidInput(text) => { format!("`{}` is not a number", text) }
+			MipsError::Diagnostics(d) => {
+				let errors = d.iter().filter(|d| d.is_error()).count();
+				format!("{} error{} while assembling", errors, if errors == 1 { "" } else { "s" })
+			}
+		}
+	}
+
+	/// The source line the error is on, if it knows one
+	pub fn line(&self) -> Option<usize> {
+		match self {
+			MipsError::SyntaxError(l) |
+			MipsError::UndefinedLabel(_, l) |
+			MipsError::BranchOutOfRange(_, l) |
+			MipsError::ImmediateOutOfRange(_, l) |
+			MipsError::UnknownMnemonic(_, l) |
+			MipsError::InvalidExpression(_, l) |
+			MipsError::IncludeNotFound(_, l) |
+			MipsError::IncludeCycle(_, l) |
+			MipsError::AtAddress(_, l, _) |
+			MipsError::UnknownRegister(_, l) |
+			MipsError::ReservedRegister(_, l) |
+			MipsError::DuplicateLabel(_, l) |
+			MipsError::InstructionInData(_, l) => { Some(*l) }
+			_ => { None }
+		}
+	}
+
+	/// The piece of the line the error is about, so it can be pointed at
+	pub(crate) fn culprit(&self) -> Option<&str> {
+		match self {
+			MipsError::UndefinedLabel(text, _) |
+			MipsError::BranchOutOfRange(text, _) |
+			MipsError::UnknownMnemonic(text, _) |
+			MipsError::InvalidExpression(text, _) |
+			MipsError::UnknownRegister(text, _) |
+			MipsError::ReservedRegister(text, _) |
+			MipsError::DuplicateLabel(text, _) |
+			MipsError::InstructionInData(text, _) => { Some(text.as_str()) }
+			_ => { None }
+		}
+	}
+}
+
+impl fmt::Display for MipsError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MipsError::InFile(file, inner) => { write!(f, "{}: {}", file, inner) }
+			MipsError::InMacro(name, l, inner) => { write!(f, "{} (in macro `{}` used on line {})", inner, name, l) }
+			MipsError::AtAddress(addr, l, inner) => { write!(f, "{} at {:#010x}, line {}", inner, addr, l) }
+			MipsError::Diagnostics(d) => {
+				for diagnostic in d.iter() {
+					writeln!(f, "{}", diagnostic)?;
+				}
+				write!(f, "{}", self.message())
+			}
+			_ => {
+				match self.line() {
+					Some(l) => { write!(f, "{} on line {}", self.message(), l) }
+					None => { write!(f, "{}", self.message()) }
+				}
+			}
+		}
+	}
+}
+
+impl Error for MipsError {}
+
+/// Why `run` or `run_until` handed control back
+#[derive(Debug, Clone)]
+pub enum StopReason {
+	Exited(i32),			// the code the program exited with
+	Breakpoint(u32),		// the PC, the instruction there hasn't run yet
+	StepLimit,
+	Exception(MipsError),	// the PC is left on the instruction that failed
+	WaitingForInput,
 }
 
 pub struct MipsInterpreter {
-	stack: Vec<u8>,
 	registers: [Register; 32],
 	pc: Register,
 	hi: Register,
 	lo: Register,
-	program: Vec<u32>,
+	memory: Memory,
 	labels: HashMap<String, u32>,
+	entry: u32,			// where the program starts
+	output: String,		// everything the program has printed
+	input: String,		// typed ahead, not read by the program yet
+	waiting_for_input: bool,	// stopped on a syscall that reads more than was typed
+	exit_code: Option<i32>,
+	instructions: u64,	// how many have run since the program was loaded
+	breakpoints: HashSet<u32>,	// kept across loads, so a restart stops in the same places
+	listing: Listing,
+	source_map: SourceMap,
+	diagnostics: Vec<Diagnostic>,	// errors and warnings from the last load
 }
 
-/// Will remove a leading '$' if it exists,
-/// and a trailing comma ',' if that also exists
-///
-/// # Arguments
-///
-/// * `inp` - The input string that will have its values trimmed
-fn remove_symbols(inp: &str) -> String {
-	let mut res;
-
-	if inp.starts_with("$") {
-		let (l, r) = inp.split_at(1); // l = "$"        r = rest
-		res = String::from(r);
-	} else {
-		res = String::from(inp);
+fn read_file(filename: &str) -> Result<Vec<u8>, MipsError> {
+	match fs::read(filename) {
+		Ok(bytes) => { Ok(bytes) }
+		Err(_) => { Err(MipsError::FileNotFound) }
 	}
-
-	if res.ends_with(",") {
-		let (l, r) = res.split_at(res.len()-1);
-		res = String::from(l);
-	}
-
-	res
 }
 
-fn get_idx_from_reg_string(terms: &mut Split) -> Option<usize> {
-	let Some(dest)  = terms.next() else { return None; };
-	let dest = remove_symbols(dest);
-	let dest = RegNames::register_align(&RegNames::str_to_enum(dest.as_str()));
-	RegNames::idx_from_enum(&dest)
-}
-
-fn load_3_terms(terms: &mut Split) -> Option<u32> {
-	let Some(dest) = get_idx_from_reg_string(terms) else {
-		return None;
-	};
-	let mut opcode: u32 = (dest >> 16) as u32;
-	let Some(first) = get_idx_from_reg_string(terms) else {
-		return None;
-	};
-	opcode = opcode | (first >> 21) as u32;
-	let Some(second) = get_idx_from_reg_string(terms) else {
-		return None;
-	};
-	opcode = opcode | (second >> 26) as u32;
-	Some(opcode)
+fn write_file(filename: &str, contents: &[u8]) -> Result<(), MipsError> {
+	match fs::write(filename, contents) {
+		Ok(_) => { Ok(()) }
+		Err(_) => { Err(MipsError::FileNotWritten(String::from(filename))) }
+	}
 }
 
-fn load_2_terms_immediate(terms: &mut Split) -> Option<u32> {
-	let Some(dest) = get_idx_from_reg_string(terms) else {
-		return None;
-	};
-	let mut opcode: u32 = (dest >> 16) as u32;
-	let Some(first) = get_idx_from_reg_string(terms) else {
-		return None;
-	};
-	opcode = opcode | (first >> 21) as u32;
-	None
-}
 
 impl MipsInterpreter {
 	pub fn display_register(&self, reg: &RegNames) -> String {
@@ -113,746 +214,618 @@ impl MipsInterpreter {
 		}
 	}
 
-	fn inst_add(&mut self, inst: u32) {
-		// $rd, $rs, $rt      =>     $rd = $rs + $rt
-
-		// BIT LOCATIONS:
-		//opcode	rs	    rt	    rd	    shift (shamt)	funct
-		//6 bits	5 bits	5 bits	5 bits	5 bits	        6 bits
-		let rs = (inst & 0b00000011111000000000000000000000) << 6;
-		let rt = (inst & 0b00000000000111110000000000000000) << 11;
-		let rd = (inst & 0b00000000000000001111100000000000) << 16;
-
-		let rs_val = self.registers[rs as usize].get_u32();
-		let rt_val = self.registers[rt as usize].get_u32();
-		self.registers[rd as usize].set_u32( rs_val + rt_val );
-	}
-
-	fn inst_jump(&mut self, addr: u32) {
-		//let index = self.labels[lbl];
-		self.pc.set_u32( addr );
-	}
-
-	fn make_add(line: &str) -> Option<u32> {
-		let mut opcode = instruction::OP_ADD;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
-	}
-
-	fn make_addi(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_ADDI;
-		Some(0)
-	}
-
-	fn make_addiu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_ADDIU;
-		Some(0)
-	}
-
-	fn make_addu(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_ADDU;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
-	}
-
-	fn make_sub(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_SUB;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
+	/// The value of a register, including PC, HI and LO
+	pub fn get_register(&self, reg: &RegNames) -> u32 {
+		match reg {
+			RegNames::PC => { self.pc.get_u32() }
+			RegNames::HI => { self.hi.get_u32() }
+			RegNames::LO => { self.lo.get_u32() }
+			_ => {
+				match RegNames::idx_from_enum(reg) {
+					Some(idx) => { self.registers[idx].get_u32() }
+					None => { 0 }
+				}
+			}
+		}
 	}
 
-	fn make_subu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SUBU;
-		Some(0)
+	/// Writes a general purpose register, $zero always stays 0
+	fn set_register(&mut self, idx: usize, val: u32) {
+		if idx != 0 {
+			self.registers[idx].set_u32( val );
+		}
 	}
 
-	fn make_and(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_AND;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
+	fn register(&self, idx: usize) -> u32 {
+		self.registers[idx].get_u32()
 	}
 
-	fn make_andi(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_ANDI;
-		Some(0)
+	pub fn new() -> MipsInterpreter {
+		MipsInterpreter {
+			registers: [
+				Register::new(RegNames::R0),
+				Register::new(RegNames::R1),
+				Register::new(RegNames::R2),
+				Register::new(RegNames::R3),
+				Register::new(RegNames::R4),
+				Register::new(RegNames::R5),
+				Register::new(RegNames::R6),
+				Register::new(RegNames::R7),
+				Register::new(RegNames::R8),
+				Register::new(RegNames::R9),
+				Register::new(RegNames::R10),
+				Register::new(RegNames::R11),
+				Register::new(RegNames::R12),
+				Register::new(RegNames::R13),
+				Register::new(RegNames::R14),
+				Register::new(RegNames::R15),
+				Register::new(RegNames::R16),
+				Register::new(RegNames::R17),
+				Register::new(RegNames::R18),
+				Register::new(RegNames::R19),
+				Register::new(RegNames::R20),
+				Register::new(RegNames::R21),
+				Register::new(RegNames::R22),
+				Register::new(RegNames::R23),
+				Register::new(RegNames::R24),
+				Register::new(RegNames::R25),
+				Register::new(RegNames::R26),
+				Register::new(RegNames::R27),
+				Register::new(RegNames::R28),
+				Register::new(RegNames::R29),
+				Register::new(RegNames::R30),
+				Register::new(RegNames::R31),
+			],
+			pc: Register::new(RegNames::PC),
+			hi: Register::new(RegNames::HI),
+			lo: Register::new(RegNames::LO),
+			memory: Memory::new(),
+			labels: HashMap::new(),
+			entry: 0,
+			output: String::new(),
+			input: String::new(),
+			waiting_for_input: false,
+			exit_code: None,
+			instructions: 0,
+			breakpoints: HashSet::new(),
+			listing: Listing::new(),
+			source_map: SourceMap::new(),
+			diagnostics: vec![],
+		}
 	}
 
-	fn make_or(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_OR;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
+	fn reset(&mut self) {
+		self.pc.set_u32( 0 );
+		self.hi.set_u32( 0 );
+		self.lo.set_u32( 0 );
+		for reg in self.registers.iter_mut() {
+			reg.set_u32( 0 );
+		}
+		self.registers[28].set_u32( memory::GLOBAL_POINTER );
+		self.registers[29].set_u32( memory::STACK_POINTER );
+		self.memory = Memory::new();
+		self.labels = HashMap::new();
+		self.entry = 0;
+		self.output = String::new();
+		self.input = String::new();
+		self.waiting_for_input = false;
+		self.exit_code = None;
+		self.instructions = 0;
+		self.listing = Listing::new();
+		self.source_map = SourceMap::new();
+		self.diagnostics = vec![];
 	}
 
-	fn make_ori(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_ORI;
-		Some(0)
+	/// Every error and warning found by the last load, warnings included when it succeeded
+	pub fn diagnostics(&self) -> &[Diagnostic] {
+		&self.diagnostics
 	}
 
-	fn make_xor(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_XOR;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
+	/// Where the code or data at a byte address was written in the source
+	pub fn source_location(&self, addr: u32) -> Option<&SourceLocation> {
+		self.source_map.lookup(addr)
 	}
 
-	fn make_xori(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_XORI;
-		Some(0)
+	/// Where the instruction the PC points at was written in the source
+	pub fn current_location(&self) -> Option<&SourceLocation> {
+		self.source_location(self.pc.get_u32())
 	}
 
-	fn make_nor(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_NOR;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
+	/// Wraps an error raised while running the instruction at `addr`
+	/// with the address and the line it came from, and the file if it has one
+	fn locate_runtime_error(&self, addr: u32, e: MipsError) -> MipsError {
+		let Some(location) = self.source_location(addr) else {
+			return e;
 		};
-		opcode = opcode | regs;
-
-		Some(opcode)
-	}
-
-	fn make_sll(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SLL;
-		Some(0)
-	}
-
-	fn make_srl(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SRL;
-		Some(0)
-	}
-
-	fn make_sra(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SRA;
-		Some(0)
-	}
-
-	fn make_sllv(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SLLV;
-		Some(0)
-	}
-
-	fn make_srlv(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SRLV;
-		Some(0)
+		let e = MipsError::AtAddress(addr, location.line, Box::new(e));
+		match &location.file {
+			Some(f) => { MipsError::InFile(f.display().to_string(), Box::new(e)) }
+			None => { e }
+		}
 	}
 
-	fn make_srav(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SRAV;
-		Some(0)
+	/// A listing of the assembled program: every line's address, machine code and source,
+	/// followed by the symbol table and a cross reference of labels.
+	pub fn listing(&self) -> String {
+		self.listing.render(&self.memory, &self.labels)
 	}
 
-	fn make_slt(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SLT;
-		Some(0)
+	/// Writes `listing()` out to a file
+	pub fn write_listing(&self, filename: &str) -> Result<(), MipsError> {
+		write_file(filename, self.listing().as_bytes())
 	}
 
-	fn make_slti(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SLTI;
-		Some(0)
+	/// The loaded program's segments, entry point and labels
+	pub fn image(&self) -> Image {
+		Image {
+			segments: self.memory.segments(),
+			entry: self.entry,
+			symbols: self.labels.clone(),
+			endian: self.memory.endian(),
+		}
 	}
 
-	fn make_sltu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SLTU;
-		Some(0)
+	/// Writes the text and the data as raw big endian binary, one file each
+	pub fn write_binary(&self, text_file: &str, data_file: &str) -> Result<(), MipsError> {
+		let image = self.image();
+		write_file(text_file, image.segment_bytes(memory::TEXT_BASE))?;
+		write_file(data_file, image.segment_bytes(memory::DATA_BASE))
 	}
 
-	fn make_sltiu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SLTIU;
-		Some(0)
+	/// Writes the program as Intel HEX
+	pub fn write_hex(&self, filename: &str) -> Result<(), MipsError> {
+		write_file(filename, self.image().to_intel_hex().as_bytes())
 	}
 
-	fn make_mult(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_MULT;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
-		};
-		opcode = opcode | regs;
-
-		Some(opcode)
+	/// Writes the program as an ELF32 executable, labels included
+	pub fn write_elf(&self, filename: &str) -> Result<(), MipsError> {
+		write_file(filename, &elf::write(&self.image()))
 	}
 
-	fn make_multu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_MULTU;
-		Some(0)
+	/// Loads a program that was already assembled, ready to run from its entry point
+	pub fn load_image(&mut self, image: Image) -> Result<(), MipsError> {
+		self.reset();
+		self.memory.set_endian(image.endian);
+		for segment in image.segments {
+			self.memory.map(segment)?;
+		}
+		// toolchains address small data relative to $gp, and say where it points with `_gp`
+		if let Some(gp) = image.symbols.get("_gp") {
+			self.registers[28].set_u32( *gp );
+		}
+		self.labels = image.symbols;
+		self.entry = image.entry;
+		self.pc.set_u32( self.entry );
+		Ok(())
 	}
 
-	fn make_div(line: &str) -> Option<u32>  {
-		let mut opcode = instruction::OP_DIV;
-		let inst_regex = Regex::new("[,\\s]+").unwrap();
-		let mut terms = inst_regex.split(line);
-		terms.next();
-		let Some(regs) = load_3_terms(&mut terms) else {
-			return None;
+	/// Loads raw binary written by `write_binary`. The data file is optional.
+	pub fn load_binary_files(&mut self, text_file: &str, data_file: Option<&str>) -> Result<(), MipsError> {
+		let text = read_file(text_file)?;
+		let data = match data_file {
+			Some(file) => { read_file(file)? }
+			None => { vec![] }
 		};
-		opcode = opcode | regs;
-
-		Some(opcode)
-	}
-
-	fn make_divu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_DIVU;
-		Some(0)
-	}
-
-	fn make_mfhi(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_MFHI;
-		Some(0)
-	}
-
-	fn make_mthi(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_MTHI;
-		Some(0)
-	}
-
-	fn make_mflo(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_MFLO;
-		Some(0)
+		self.load_image(Image::from_raw(&text, &data)?)
 	}
 
-	fn make_mtlo(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_MTLO;
-		Some(0)
+	pub fn load_hex_file(&mut self, filename: &str) -> Result<(), MipsError> {
+		let text = String::from_utf8_lossy(&read_file(filename)?).into_owned();
+		self.load_image(Image::from_intel_hex(&text)?)
 	}
 
-	fn make_li(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_ORI;
-		Some(0)
+	/// Loads a statically linked MIPS32 executable, big or little endian.
+	/// Branches have no delay slot here, see `elf.rs` for what that means for compiled code.
+	pub fn load_elf_file(&mut self, filename: &str) -> Result<(), MipsError> {
+		self.load_image(elf::read(&read_file(filename)?)?)
 	}
 
-	fn make_lui(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_ORI;
-		Some(0)
+	/// How many bytes of static data the program has, starting at `memory::DATA_BASE`
+	pub fn data_size(&self) -> u32 {
+		self.memory.static_size()
 	}
 
-	// memory related
-	fn make_lw(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_LW;
-		Some(0)
+	pub fn memory(&self) -> &Memory {
+		&self.memory
 	}
 
-	fn make_lh(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_LH;
-		Some(0)
+	/// The byte address a label points at
+	pub fn label_address(&self, label: &str) -> Option<u32> {
+		self.labels.get(label).copied()
 	}
 
-	fn make_lhu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_LHU;
-		Some(0)
+	/// Reads the word at a byte address, which has to be a multiple of 4
+	pub fn read_word(&self, addr: u32) -> Option<u32> {
+		self.memory.read_word(addr).ok()
 	}
 
-	fn make_lb(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_LB;
-		Some(0)
+	/// Reads the single precision float at a byte address, which has to be a multiple of 4
+	pub fn read_f32(&self, addr: u32) -> Option<f32> {
+		Some(f32::from_bits(self.read_word(addr)?))
 	}
 
-	fn make_lbu(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_LBU;
-		Some(0)
-	}
-
-	fn make_sw(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SW;
-		Some(0)
-	}
-
-	fn make_sh(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SH;
-		Some(0)
-	}
-
-	fn make_sb(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SB;
-		Some(0)
+	/// Reads the double precision float at a byte address, which has to be a multiple of 8.
+	/// The high word comes first.
+	pub fn read_f64(&self, addr: u32) -> Option<f64> {
+		if addr % 8 != 0 {
+			return None;
+		}
+		let high = self.read_word(addr)? as u64;
+		let low = self.read_word(addr + 4)? as u64;
+		Some(f64::from_bits(high << 32 | low))
 	}
 
-	fn make_beq(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_BEQ;
-		Some(0)
+	/// The instruction at `addr` as assembly, with targets shown by label
+	pub fn disassemble(&self, addr: u32) -> Option<String> {
+		let word = self.memory.read_word(addr).ok()?;
+		Some(Disassembler::with_labels(&self.labels).disassemble(word, addr))
 	}
 
-	fn make_bne(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_BNE;
-		Some(0)
+	/// The word at `addr` as an unsigned int and as a float, for displaying data memory
+	pub fn display_memory(&self, addr: u32) -> String {
+		match (self.read_word(addr), self.read_f32(addr)) {
+			(Some(word), Some(float)) => { format!("{:#010x}: {} / {}", addr, word, float) }
+			_ => { format!("{:#010x}: -", addr) }
+		}
 	}
 
-	fn make_jr(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_JR;
-		Some(0)
+	/// Every word of the static data, then every instruction, one number per line
+	pub fn get_program_contents(&self) -> String {
+		let data = &self.memory.data()[..self.memory.static_size() as usize];
+		let data_words = data.chunks(4).map(|chunk| {
+			chunk.iter().enumerate().fold(0, |word, (i, b)| MipsInterpreter::add_byte_to_line(word, i as u32, *b))
+		});
+		let mut s = String::with_capacity((data.len() / 4 + self.memory.text().len())*3);
+		for line in data_words.chain(self.memory.text().iter().copied()) {
+			s.push_str(format!("{}\n", line).as_str());
+		}
+		s
 	}
 
-	fn make_j(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_J;
-		Some(0)
+	/// Runs the instruction the PC points at.
+	/// Does nothing once the program has exited, or while it waits for input.
+	pub fn process_line(&mut self) -> Result<(), MipsError> {
+		if self.exit_code.is_some() {
+			return Ok(());
+		}
+		let addr = self.pc.get_u32();
+		// a PC outside of the code has no line to blame
+		let inst = self.memory.fetch(addr)?;
+		self.execute_line(inst).map_err(|e| self.locate_runtime_error(addr, e))?;
+		// a syscall waiting for input runs again, and is only counted then
+		if !self.waiting_for_input {
+			self.instructions += 1;
+		}
+		Ok(())
 	}
 
-	fn make_jal(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_JAL;
-		Some(0)
+	/// Runs at most `limit` instructions, stopping early if the program exits, fails,
+	/// waits for input or reaches a breakpoint
+	pub fn run(&mut self, limit: u64) -> StopReason {
+		self.run_steps(Some(limit), |_| false)
 	}
 
-	fn make_syscall(line: &str) -> Option<u32>  {
-		let opcode = instruction::OP_SYSCALL;
-		Some(0)
+	/// Runs until `predicate` holds before an instruction, which stops like a breakpoint there,
+	/// or until the program exits, fails, waits for input or reaches a breakpoint
+	pub fn run_until<F: FnMut(&MipsInterpreter) -> bool>(&mut self, predicate: F) -> StopReason {
+		self.run_steps(None, predicate)
 	}
 
-	pub fn new() -> MipsInterpreter {
-		MipsInterpreter {
-			stack: vec![],
-			registers: [
-				Register::new(RegNames::R0),
-				Register::new(RegNames::R1),
-				Register::new(RegNames::R2),
-				Register::new(RegNames::R3),
-				Register::new(RegNames::R4),
-				Register::new(RegNames::R5),
-				Register::new(RegNames::R6),
-				Register::new(RegNames::R7),
-				Register::new(RegNames::R8),
-				Register::new(RegNames::R9),
-				Register::new(RegNames::R10),
-				Register::new(RegNames::R11),
-				Register::new(RegNames::R12),
-				Register::new(RegNames::R13),
-				Register::new(RegNames::R14),
-				Register::new(RegNames::R15),
-				Register::new(RegNames::R16),
-				Register::new(RegNames::R17),
-				Register::new(RegNames::R18),
-				Register::new(RegNames::R19),
-				Register::new(RegNames::R20),
-				Register::new(RegNames::R21),
-				Register::new(RegNames::R22),
-				Register::new(RegNames::R23),
-				Register::new(RegNames::R24),
-				Register::new(RegNames::R25),
-				Register::new(RegNames::R26),
-				Register::new(RegNames::R27),
-				Register::new(RegNames::R28),
-				Register::new(RegNames::R29),
-				Register::new(RegNames::R30),
-				Register::new(RegNames::R31),
-			],
-			pc: Register::new(RegNames::PC),
-			hi: Register::new(RegNames::HI),
-			lo: Register::new(RegNames::LO),
-			program: vec![],
-			labels: HashMap::new(),
+	fn run_steps<F: FnMut(&MipsInterpreter) -> bool>(&mut self, limit: Option<u64>, mut stop: F) -> StopReason {
+		let mut steps = 0;
+		loop {
+			if let Some(code) = self.exit_code {
+				return StopReason::Exited(code);
+			}
+			let pc = self.pc.get_u32();
+			// the first instruction always runs, so a program stopped on a breakpoint can carry on
+			if steps > 0 && (self.breakpoints.contains(&pc) || stop(self)) {
+				return StopReason::Breakpoint(pc);
+			}
+			if matches!(limit, Some(limit) if steps >= limit) {
+				return StopReason::StepLimit;
+			}
+			if let Err(e) = self.process_line() {
+				return StopReason::Exception(e);
+			}
+			steps += 1;
+			if self.waiting_for_input {
+				return StopReason::WaitingForInput;
+			}
 		}
 	}
 
-	fn reset(&mut self) {
-		self.pc.set_u32( 0 );
-		self.program = vec![];
-		self.labels = HashMap::new();
+	/// How many instructions have run since the program was loaded
+	pub fn instruction_count(&self) -> u64 {
+		self.instructions
 	}
 
-	fn get_opcode_from_instruction(code: u32) -> u32 {
-		let first = code & instruction::OP_FIRST_CODE;
-		if first != 0 {
-			// We use the leading bits
-			first
-		} else {
-			// We use the trailing bits
-			code & instruction::OP_SECOND_CODE
-		}
+	/// Stops `run` and `run_until` before the instruction at `addr`.
+	/// Breakpoints stay set when a program is loaded again.
+	pub fn add_breakpoint(&mut self, addr: u32) {
+		self.breakpoints.insert(addr);
 	}
 
-	pub fn get_program_contents(&self) -> String {
-		let mut s = String::with_capacity(self.program.len()*3);
-		for line in self.program.iter() {
-			s.push_str(format!("{}\n", line).as_str());
-		}
-		s
+	/// Returns whether there was a breakpoint at `addr`
+	pub fn remove_breakpoint(&mut self, addr: u32) -> bool {
+		self.breakpoints.remove(&addr)
 	}
 
-	pub fn process_line(&mut self) -> Result<(), MipsError> {
-		let inst= self.program[self.pc.get_u32() as usize];
-		let opcode = MipsInterpreter::get_opcode_from_instruction(inst);
-		match opcode {
-			instruction::OP_ADD => { self.inst_add(inst); }
-			instruction::OP_J => { self.inst_jump(inst); }
-			_ => { return Err(MipsError::UnknownInstruction(inst)); }
+	fn execute_line(&mut self, inst: u32) -> Result<(), MipsError> {
+		let pc = self.pc.get_u32();
+		// where the next instruction comes from, when a branch or jump is taken
+		let next = match DecodedInstruction::decode(inst) {
+			DecodedInstruction::R { rs, rt, rd, shamt, funct } => { self.execute_register(pc, funct, rs, rt, rd, shamt)? }
+			DecodedInstruction::I { opcode, rs, rt, imm } => { self.execute_immediate(pc, opcode << 26, rs, rt, imm)? }
+			// jumps keep the upper 4 bits of the PC
+			DecodedInstruction::J { opcode, target } => {
+				if opcode << 26 == instruction::OP_JAL {
+					self.set_register(31, pc.wrapping_add(4));
+				}
+				Some((pc.wrapping_add(4) & 0xF0000000) | target << 2)
+			}
+		};
+		match next {
+			Some(addr) => { self.pc.set_u32( addr ); }
+			None => { self.pc.add_u32(4); }
 		}
-		self.pc.add_u32(4);
 
 		Ok(())
 	}
 
-	fn read_val_or_immediate(vars: &mut HashMap<String, i32>,
-							 labels: &mut HashMap<String, u32>,
-							 terms: &mut Split) -> i32 {
-		let val = terms.next().unwrap();
-		match vars.entry(String::from(val)) {
-			Entry::Occupied(e) => {
-				*e.get() as i32
+	/// Runs an R format instruction, returning where it jumps to if it does
+	fn execute_register(&mut self, pc: u32, funct: u32, rs: usize, rt: usize, rd: usize, shamt: u32)
+		-> Result<Option<u32>, MipsError> {
+		let (s, t) = (self.register(rs), self.register(rt));
+		match funct {
+			// add and sub trap on signed overflow without writing $rd, the unsigned forms wrap
+			instruction::OP_ADD => {
+				let Some(sum) = (s as i32).checked_add(t as i32) else {
+					return Err(MipsError::ArithmeticOverflow);
+				};
+				self.set_register(rd, sum as u32);
+			}
+			instruction::OP_ADDU => { self.set_register(rd, s.wrapping_add(t)); }
+			instruction::OP_SUB => {
+				let Some(difference) = (s as i32).checked_sub(t as i32) else {
+					return Err(MipsError::ArithmeticOverflow);
+				};
+				self.set_register(rd, difference as u32);
 			}
-			Entry::Vacant(_) => {
-				match labels.entry(String::from(val)) {
-					Entry::Occupied(e) => {
-						*e.get() as i32
+			instruction::OP_SUBU => { self.set_register(rd, s.wrapping_sub(t)); }
+			instruction::OP_AND => { self.set_register(rd, s & t); }
+			instruction::OP_OR => { self.set_register(rd, s | t); }
+			instruction::OP_XOR => { self.set_register(rd, s ^ t); }
+			instruction::OP_NOR => { self.set_register(rd, !(s | t)); }
+			// shifts, the variable ones only use the low 5 bits of $rs
+			instruction::OP_SLL => { self.set_register(rd, t << shamt); }
+			instruction::OP_SRL => { self.set_register(rd, t >> shamt); }
+			instruction::OP_SRA => { self.set_register(rd, ((t as i32) >> shamt) as u32); }
+			instruction::OP_SLLV => { self.set_register(rd, t << (s & 0x1F)); }
+			instruction::OP_SRLV => { self.set_register(rd, t >> (s & 0x1F)); }
+			instruction::OP_SRAV => { self.set_register(rd, ((t as i32) >> (s & 0x1F)) as u32); }
+			instruction::OP_SLT => { self.set_register(rd, ((s as i32) < (t as i32)) as u32); }
+			instruction::OP_SLTU => { self.set_register(rd, (s < t) as u32); }
+			// HI holds the upper word of a product, or the remainder of a division
+			instruction::OP_MULT => {
+				let product = (s as i32 as i64 * t as i32 as i64) as u64;
+				self.hi.set_u32( (product >> 32) as u32 );
+				self.lo.set_u32( product as u32 );
+			}
+			instruction::OP_MULTU => {
+				let product = s as u64 * t as u64;
+				self.hi.set_u32( (product >> 32) as u32 );
+				self.lo.set_u32( product as u32 );
+			}
+			instruction::OP_DIV => {
+				// the result of dividing by zero isn't defined, HI and LO are left alone.
+				// i32::MIN / -1 overflows too, and wraps back to i32::MIN with no remainder
+				if t != 0 {
+					self.hi.set_u32( (s as i32).wrapping_rem(t as i32) as u32 );
+					self.lo.set_u32( (s as i32).wrapping_div(t as i32) as u32 );
+				}
+			}
+			instruction::OP_DIVU => {
+				if t != 0 {
+					self.hi.set_u32( s % t );
+					self.lo.set_u32( s / t );
+				}
+			}
+			instruction::OP_MFHI => { self.set_register(rd, self.hi.get_u32()); }
+			instruction::OP_MFLO => { self.set_register(rd, self.lo.get_u32()); }
+			instruction::OP_MTHI => { self.hi.set_u32( s ); }
+			instruction::OP_MTLO => { self.lo.set_u32( s ); }
+			instruction::OP_JR => { return Ok(Some(s)); }
+			instruction::OP_SYSCALL => {
+				// a syscall that has to wait runs again once there is input
+				if !self.syscall()? {
+					return Ok(Some(pc));
+				}
+			}
+			_ => { return Err(MipsError::UnknownInstruction(DecodedInstruction::r(funct, rs, rt, rd, shamt).encode())); }
+		}
+		Ok(None)
+	}
+
+	/// Runs an I format instruction, returning where it branches to if it does
+	fn execute_immediate(&mut self, pc: u32, op: u32, rs: usize, rt: usize, imm: u16)
+		-> Result<Option<u32>, MipsError> {
+		let (s, t) = (self.register(rs), self.register(rt));
+		let simm = imm as i16 as i32 as u32;	// sign extended
+		let uimm = imm as u32;					// zero extended
+		// loads and stores are addressed by $rs plus the sign extended offset
+		let addr = s.wrapping_add(simm);
+		match op {
+			instruction::OP_ADDI => {
+				let Some(sum) = (s as i32).checked_add(simm as i32) else {
+					return Err(MipsError::ArithmeticOverflow);
+				};
+				self.set_register(rt, sum as u32);
+			}
+			instruction::OP_ADDIU => { self.set_register(rt, s.wrapping_add(simm)); }
+			instruction::OP_ANDI => { self.set_register(rt, s & uimm); }
+			instruction::OP_ORI => { self.set_register(rt, s | uimm); }
+			instruction::OP_XORI => { self.set_register(rt, s ^ uimm); }
+			instruction::OP_LUI => { self.set_register(rt, uimm << 16); }
+			// sltiu sign extends its immediate and then compares unsigned
+			instruction::OP_SLTI => { self.set_register(rt, ((s as i32) < (simm as i32)) as u32); }
+			instruction::OP_SLTIU => { self.set_register(rt, (s < simm) as u32); }
+			instruction::OP_LW => { self.set_register(rt, self.memory.read_word(addr)?); }
+			instruction::OP_LH => { self.set_register(rt, self.memory.read_half(addr)? as i16 as i32 as u32); }
+			instruction::OP_LHU => { self.set_register(rt, self.memory.read_half(addr)? as u32); }
+			instruction::OP_LB => { self.set_register(rt, self.memory.read_byte(addr)? as i8 as i32 as u32); }
+			instruction::OP_LBU => { self.set_register(rt, self.memory.read_byte(addr)? as u32); }
+			instruction::OP_SW => { self.memory.write_word(addr, t)?; }
+			instruction::OP_SH => { self.memory.write_half(addr, t as u16)?; }
+			instruction::OP_SB => { self.memory.write_byte(addr, t as u8)?; }
+			// branches count words from the instruction after them
+			instruction::OP_BEQ | instruction::OP_BNE => {
+				if (s == t) == (op == instruction::OP_BEQ) {
+					return Ok(Some(pc.wrapping_add(4).wrapping_add(simm << 2)));
+				}
+			}
+			_ => { return Err(MipsError::UnknownInstruction(DecodedInstruction::i(op, rs, rt, imm).encode())); }
+		}
+		Ok(None)
+	}
+
+	/// Runs the service numbered by $v0, using the codes MARS does.
+	/// Returns false when it needs input that hasn't been typed yet.
+	fn syscall(&mut self) -> Result<bool, MipsError> {
+		let a0 = self.register(4);
+		let a1 = self.register(5);
+		self.waiting_for_input = false;
+		match self.register(2) {
+			1 => { self.output.push_str(&(a0 as i32).to_string()); }	// print int
+			4 => {															// print string
+				let mut bytes = vec![];
+				let mut addr = a0;
+				loop {
+					let b = self.memory.read_byte(addr)?;
+					if b == 0 {
+						break;
 					}
-					Entry::Vacant(_) => {
-						val.parse::<i32>().unwrap()
+					bytes.push(b);
+					addr = addr.wrapping_add(1);
+				}
+				self.output.push_str(&String::from_utf8_lossy(&bytes));
+			}
+			5 => {															// read int
+				let Some(line) = self.read_input_line() else {
+					return Ok(false);
+				};
+				let Ok(value) = line.trim().parse::<i32>() else {
+					return Err(MipsError::InvalidInput(line));
+				};
+				self.set_register(2, value as u32);
+			}
+			8 => {															// read string
+				// reads up to $a1 - 1 characters, the newline included, and ends them with a NULL
+				let Some(line) = self.read_input_line() else {
+					return Ok(false);
+				};
+				if a1 > 0 {
+					let mut bytes: Vec<u8> = line.bytes().chain(iter::once(b'\n')).take(a1 as usize - 1).collect();
+					bytes.push(0);
+					for (i, b) in bytes.into_iter().enumerate() {
+						self.memory.write_byte(a0.wrapping_add(i as u32), b)?;
 					}
 				}
 			}
+			9 => {															// sbrk
+				let addr = self.memory.allocate(a0)?;
+				self.set_register(2, addr);
+			}
+			10 => { self.exit_code = Some(0); }							// exit
+			11 => { self.output.push(a0 as u8 as char); }					// print char
+			12 => {															// read char
+				let Some(c) = self.input.chars().next() else {
+					self.waiting_for_input = true;
+					return Ok(false);
+				};
+				self.input.remove(0);
+				self.set_register(2, c as u32);
+			}
+			17 => { self.exit_code = Some(a0 as i32); }					// exit with a code
+			service => { return Err(MipsError::UnknownSyscall(service)); }
 		}
+		Ok(true)
 	}
 
-	fn load_zero_into_line(line: u32, idx: usize) -> u32 {
-		match idx {
-			0 => { line & 0b00000000111111111111111111111111 },
-			1 => { line & 0b11111111000000001111111111111111 },
-			2 => { line & 0b11111111111111110000000011111111 },
-			3 => { line & 0b11111111111111111111111100000000 },
-			_ => { 0 /* THIS SHOULD NEVER BE REACHED*/ }
-		}
+	/// Takes one line of input, without its newline, once a whole line has been typed
+	fn read_input_line(&mut self) -> Option<String> {
+		let Some(end) = self.input.find('\n') else {
+			self.waiting_for_input = true;
+			return None;
+		};
+		let line = self.input[..end].trim_end_matches('\r').to_string();
+		self.input.drain(..=end);
+		Some(line)
 	}
 
-	fn get_byte_segment_u32(line: u32, idx: usize) -> u8 {
-		let res = match idx {
-			0 => { (line & 0b11111111000000000000000000000000) >> 24 },
-			1 => { (line & 0b00000000111111110000000000000000) >> 16 },
-			2 => { (line & 0b00000000000000001111111100000000) >> 8 },
-			3 => { (line & 0b00000000000000000000000011111111) },
-			_ => { 0 /* THIS SHOULD NEVER BE REACHED*/ }
-		} as u8;
-		// rust refuses to handle this unless the data is named
-		// so we're naming it just to return it
-		res
+	/// Types input for the program to read, lines end with '\n'
+	pub fn provide_input(&mut self, text: &str) {
+		self.input.push_str(text);
 	}
 
-	fn get_byte_segment_u16(line: u16, idx: usize) -> u8 {
-		let res = match idx {
-			0 => { (line & 0b1111111100000000) >> 8 },
-			1 => { (line & 0b0000000011111111)  },
-			_ => { 0 /* THIS SHOULD NEVER BE REACHED*/ }
-		} as u8;
-		// rust refuses to handle this unless the data is named
-		// so we're naming it just to return it
-		res
+	/// Whether the program is stuck on a syscall that needs more input
+	pub fn waiting_for_input(&self) -> bool {
+		self.waiting_for_input
+	}
+
+	/// Everything the program has printed so far
+	pub fn output(&self) -> &str {
+		&self.output
+	}
+
+	/// The code the program exited with, once it has
+	pub fn exit_code(&self) -> Option<i32> {
+		self.exit_code
 	}
 
 	fn add_byte_to_line(line: u32, pointer: u32, byte: u8) -> u32 {
 		match pointer % 4 {
-			0 => { line & (0b00000000111111111111111111111111 | (byte as u32) << 24) },
-			1 => { line & (0b11111111000000001111111111111111 | (byte as u32) << 16) },
-			2 => { line & (0b11111111111111110000000011111111 | (byte as u32) << 8) },
-			3 => { line & (0b11111111111111111111111100000000 | byte as u32) },
+			0 => { (line & 0b00000000111111111111111111111111) | (byte as u32) << 24 },
+			1 => { (line & 0b11111111000000001111111111111111) | (byte as u32) << 16 },
+			2 => { (line & 0b11111111111111110000000011111111) | (byte as u32) << 8 },
+			3 => { (line & 0b11111111111111111111111100000000) | byte as u32 },
 			_ => { 0 /* Not actually reachable, but rust requires it. */ }
 		}
 	}
 
+	/// Assembles a program file and loads it, `.include` paths are relative to the file
 	pub fn load_program_file(&mut self, filename: &str) -> Result<(), MipsError> {
-		println!("Loading {}", filename);
-		if let Ok(contents) = fs::read_to_string(filename) {
-			self.load_program(&contents.as_str())
-		} else {
-			Err(MipsError::FileNotFound)
-		}
+		let result = Assembler::new().assemble_file(filename);
+		self.load_assembled(result)
 	}
 
+	/// Assembles source and loads it
 	pub fn load_program(&mut self, contents: &str) -> Result<(), MipsError> {
-		let mut state = LoadingState::FileOpen;
-		let mut variables: HashMap<String, i32> = HashMap::new();
-		let mut labels: HashMap<String, u32> = HashMap::new();
-
-		self.reset();
-		let mut current_line: u32 = 0;		// line of meaningful text in the ASM
-
-		// keeps track of every byte, not every line
-		// this just rotates between 0-3 for the 32 bits per line
-		let mut data_pointer: u32 = 0;
-		for line in contents.lines() {
-			println!("{} ({}): {}", data_pointer, data_pointer%4, line);
-			let line = line.trim();
-			if line.starts_with("#") || line.eq("") { continue; }
-
-			// split on 'whitespace' and '='
-			let inst_regex = Regex::new("[,\\s]+").unwrap();
-			let assign_regex = Regex::new("[=\\s]+").unwrap();
-			let label_regex = Regex::new("[A-Za-z]+:").unwrap();
-			let quote_regex = Regex::new("\"").unwrap();
-			if label_regex.is_match(line) {
-				if line.starts_with("main:") {
-					if data_pointer % 4 != 0 {
-						return Err(MipsError::InvalidMain);
-					}
-				}
-				labels.insert(String::from(line), data_pointer);
-				continue;
-			}
-
-			// match within
-			match state {
-
-				LoadingState::FileOpen => {
-					if line == ".data" {
-						state = LoadingState::Data;
-						continue;
-					}
-					let mut terms = assign_regex.split(line);
-					let lbl = terms.next().unwrap().trim();
-					let val = terms.next().unwrap().trim();
-					variables.insert(lbl.parse().unwrap(), val.parse().unwrap());
-				}
-
-				LoadingState::Data => {
-					if line == ".text" {
-						state = LoadingState::Code;
-						continue;
-					}
-
-					let mut terms = assign_regex.split(line);
-					if line.starts_with(".align") {
-						let _ = terms.next(); // the .assign keyword
-						// the value is in terms of halfwords. I don't know why, it just is.
-						let val: u32 = terms.next().unwrap().trim().parse().unwrap();
-						if data_pointer % (2 * val) != 0 {
-							self.program.push(current_line);
-							data_pointer = (self.program.len() * 4) as u32;
-							current_line = 0;
-							continue;
-						}
-					} else if line.starts_with(".space") { /* 8 bits times the size */
-						let _ = terms.next(); // the .assign keyword
-						let val = MipsInterpreter::read_val_or_immediate(&mut variables, &mut labels, &mut terms);
-						for i in 0..val as usize {
-							// loads in a SINGLE BYTE into where-ever the data_pointer says
-							current_line = MipsInterpreter::load_zero_into_line(current_line, i%4);
-							data_pointer += 1;
-							if data_pointer % 4 == 0 {
-								self.program.push(current_line);
-								current_line = 0;
-							}
-						}
-					} else if line.starts_with(".word") { /* 32 bits */
-						let _ = terms.next(); // we can skip the ".word" at the start
-						let val = MipsInterpreter::read_val_or_immediate(&mut variables, &mut labels, &mut terms);
-						for i in 0..4 {
-							let b = MipsInterpreter::get_byte_segment_u32(val as u32, i);
-							current_line = MipsInterpreter::add_byte_to_line(current_line, data_pointer, b);
-							data_pointer += 1;
-							if data_pointer % 4 == 0 {
-								self.program.push(current_line);
-								current_line = 0;
-							}
-						}
-					} else if line.starts_with(".halfword") { /* 16 bits */
-						let _ = terms.next(); // we can skip the ".word" at the start
-						let val = MipsInterpreter::read_val_or_immediate(&mut variables, &mut labels, &mut terms);
-						for i in 0..2 {
-							let b = MipsInterpreter::get_byte_segment_u16(val as u16, i);
-							current_line = MipsInterpreter::add_byte_to_line(current_line, data_pointer, b);
-							data_pointer += 1;
-							if data_pointer % 4 == 0 {
-								self.program.push(current_line);
-								current_line = 0;
-							}
-						}
-					} else if line.starts_with(".ascii") { /* 8 bits per character */
-						let mut terms = quote_regex.split(line);
-						let _ = terms.next(); // we can skip the .asciiz
-						// and just look at the contents of the quote
-						let contents = terms.next().unwrap();
-						let mut escape_flag = false;
-						for mut c in contents.chars() {
-							if c == '\\' {
-								escape_flag = true;
-								continue;
-							}
-							if escape_flag && c == 'n' {
-								c = '\n';
-							}
-							let b = c as u8;
-							current_line = MipsInterpreter::add_byte_to_line(current_line, data_pointer, b);
-							data_pointer += 1;
-							if data_pointer % 4 == 0 {
-								self.program.push(current_line);
-								current_line = 0;
-							}
-						}
-					} else if line.starts_with(".asciiz") { /* 8 bits per character plus the empty */
-						let mut terms = quote_regex.split(line);
-						let _ = terms.next(); // we can skip the .asciiz
-						// and just look at the contents of the quote
-						let contents = terms.next().unwrap();
-						for c in contents.chars() {
-							let b = c.to_digit(10).unwrap() as u8;
-							current_line = MipsInterpreter::add_byte_to_line(current_line, data_pointer, b);
-							data_pointer += 1;
-							if data_pointer % 4 == 0 {
-								self.program.push(current_line);
-								current_line = 0;
-							}
-						}
-						// 0 is the NULL character for ASCII
-						current_line = MipsInterpreter::add_byte_to_line(current_line, data_pointer, 0);
-						data_pointer += 1;
-						if data_pointer % 4 == 0 {
-							self.program.push(current_line);
-							current_line = 0;
-						}
-					} else if line.starts_with(".byte") { /* 8 bits */
-						let _ = terms.next(); // we can skip the ".byte" at the start
-						let val = MipsInterpreter::read_val_or_immediate(&mut variables, &mut labels, &mut terms);
-						//
-						current_line = MipsInterpreter::add_byte_to_line(current_line, data_pointer, val as u8);
-						data_pointer += 1;
-						if data_pointer % 4 == 0 {
-							self.program.push(current_line);
-							current_line = 0;
-						}
-					} else if line.starts_with(".float") {
-						// DISABLED
-					} else if line.starts_with(".double") {
-						// DISABLED
-					}
-				}
-
-				LoadingState::Code => {
-					let mut terms = inst_regex.split(line);
-					if line.starts_with(".align") {
-						let _ = terms.next(); // the .assign keyword
-						// the value is in terms of halfwords. I don't know why, it just is.
-						let val: u32 = terms.next().unwrap().trim().parse().unwrap();
-						if data_pointer % (2 * val) != 0 {
-							self.program.push(current_line);
-							data_pointer = (self.program.len() * 4) as u32;
-							current_line = 0;
-							continue;
-						}
-					}
+		let result = Assembler::new().assemble(contents);
+		self.load_assembled(result)
+	}
 
-					let op = terms.next().unwrap();
-					let opcode = OPName::from(op);
-					let mut result = 0;
-					let func = match opcode {
-						instruction::OP_ADD => { MipsInterpreter::make_add }
-						instruction::OP_ADDI => { MipsInterpreter::make_addi }
-						instruction::OP_ADDIU => { MipsInterpreter::make_addiu }
-						instruction::OP_ADDU => { MipsInterpreter::make_addu }
-						instruction::OP_SUB => { MipsInterpreter::make_sub }
-						instruction::OP_SUBU => { MipsInterpreter::make_subu }
-						instruction::OP_AND => { MipsInterpreter::make_and }
-						instruction::OP_ANDI => { MipsInterpreter::make_andi }
-						instruction::OP_OR => { MipsInterpreter::make_or }
-						instruction::OP_ORI => {
-							if op == "li" {
-								MipsInterpreter::make_li
-							} else {
-								MipsInterpreter::make_ori
-							}
-						}
-						instruction::OP_XOR => { MipsInterpreter::make_xor }
-						instruction::OP_XORI => { MipsInterpreter::make_xori }
-						instruction::OP_NOR => { MipsInterpreter::make_nor }
-						instruction::OP_SLL => { MipsInterpreter::make_sll }
-						instruction::OP_SRL => { MipsInterpreter::make_srl }
-						instruction::OP_SRA => { MipsInterpreter::make_sra }
-						instruction::OP_SLLV => { MipsInterpreter::make_sllv }
-						instruction::OP_SRLV => { MipsInterpreter::make_srlv }
-						instruction::OP_SRAV => { MipsInterpreter::make_srav }
-						instruction::OP_SLT => { MipsInterpreter::make_slt }
-						instruction::OP_SLTI => { MipsInterpreter::make_slti }
-						instruction::OP_SLTU => { MipsInterpreter::make_sltu }
-						instruction::OP_SLTIU => { MipsInterpreter::make_sltiu }
-						instruction::OP_MULT => { MipsInterpreter::make_mult }
-						instruction::OP_MULTU => { MipsInterpreter::make_multu }
-						instruction::OP_DIV => { MipsInterpreter::make_div }
-						instruction::OP_DIVU => { MipsInterpreter::make_divu }
-						instruction::OP_MFHI => { MipsInterpreter::make_mfhi }
-						instruction::OP_MTHI => { MipsInterpreter::make_mthi }
-						instruction::OP_MFLO => { MipsInterpreter::make_mflo }
-						instruction::OP_MTLO => { MipsInterpreter::make_mtlo }
-						instruction::OP_LUI => { MipsInterpreter::make_lui }
-// memory related
-						instruction::OP_LW => { MipsInterpreter::make_lw }
-						instruction::OP_LH => { MipsInterpreter::make_lh }
-						instruction::OP_LHU => { MipsInterpreter::make_lhu }
-						instruction::OP_LB => { MipsInterpreter::make_lb }
-						instruction::OP_LBU => { MipsInterpreter::make_lbu }
-						instruction::OP_SW => { MipsInterpreter::make_sw }
-						instruction::OP_SH => { MipsInterpreter::make_sh }
-						instruction::OP_SB => { MipsInterpreter::make_sb }
-// branching
-						instruction::OP_BEQ => { MipsInterpreter::make_beq }
-						instruction::OP_BNE => { MipsInterpreter::make_bne }
-						instruction::OP_JR => { MipsInterpreter::make_jr }
-						instruction::OP_J => { MipsInterpreter::make_j }
-						instruction::OP_JAL => { MipsInterpreter::make_jal }
-						instruction::OP_SYSCALL => { MipsInterpreter::make_syscall }
-						_ => { return Err(MipsError::UnknownInstruction(opcode)); }
-					};
-					match func(line) {
-						Some(res) => { result = res; }
-						None => { return Err(MipsError::SyntaxError(current_line as usize)); }
-					}
+	/// Loads an assembled program, ready to run from `main`.
+	/// Loading it again starts it over without assembling it again.
+	pub fn load(&mut self, program: &Program) -> Result<(), MipsError> {
+		self.load_image(program.image.clone())?;
+		self.listing = program.listing.clone();
+		self.source_map = program.source_map.clone();
+		self.diagnostics = program.diagnostics.clone();
+		Ok(())
+	}
 
-					self.program.push(result);
-					data_pointer = (self.program.len() * 4) as u32;
+	/// Loads a program that assembled, or keeps the diagnostics of one that didn't
+	fn load_assembled(&mut self, result: Result<Program, MipsError>) -> Result<(), MipsError> {
+		match result {
+			Ok(program) => { self.load(&program) }
+			Err(e) => {
+				self.reset();
+				if let MipsError::Diagnostics(d) = &e {
+					self.diagnostics = d.clone();
 				}
+				Err(e)
 			}
-		} // for each line
-
-		// There MIGHT be lingering bits, but shouldn't be there!
-		// Check and push onto the program just in case
-		if data_pointer % 4 != 0 {
-			self.program.push(current_line);
-		}
-
-		match labels.entry(String::from("main")) {
-			Entry::Occupied(v) => {
-				self.pc.set_u32( *v.get() );
-				Ok(())
-			}
-			Entry::Vacant(e) => { Err(MipsError::MissingMain) }
 		}
 	}
 }
@@ -1,9 +1,11 @@
 use iced::{Align, Application, Button, button, Clipboard, Color, Column, Command, Container, container, Element, executor, Font, Length, Row, Scrollable, scrollable, Settings, Text, VerticalAlignment};
 
-use crate::mips_int::{MipsError, MipsInterpreter};
+use crate::memory;
+use crate::mips_int::MipsInterpreter;
 use crate::register::RegNames;
 
 const ASM_FILEPATH: &str = "data/game.asm";
+const DATA_ROWS: usize = 32;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Message {
@@ -32,15 +34,10 @@ impl Application for MipsWindow {
 
     fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
         let mut backend = MipsInterpreter::new();
-        if let Err(e) = backend.load_program(ASM_FILEPATH) {
-            match e {
-                MipsError::UnknownInstruction(ui) => { println!("Unknown Instruction on line: {}", ui); }
-                MipsError::SyntaxError(l) => { println!("Error parsing ASM on line: {}", l); }
-                MipsError::MissingMain => { println!("Missing 'main' indicator."); }
-                MipsError::InvalidMain => { println!("Invalid 'main' indicator."); }
-                MipsError::UnalignedBytes => { println!("Unaligned bytes."); }
-                MipsError::FileNotFound => { println!("Program file unable to be opened."); }
-            }
+        // a program that doesn't assemble says why where its output would go
+        let output = match backend.load_program_file(ASM_FILEPATH) {
+            Ok(_) => { String::from("Output...") }
+            Err(e) => { e.to_string() }
         };
 
         (MipsWindow {
@@ -48,7 +45,7 @@ impl Application for MipsWindow {
             load_button: button::State::new(),
             next_button: button::State::new(),
             go_button: button::State::new(),
-            output: String::from("Output..."),
+            output,
         }, Command::none())
     }
 
@@ -61,8 +58,9 @@ impl Application for MipsWindow {
             Message::BtnClick => {}
             Message::CloseFocused => {}
             Message::LoadASM => {
-                if let Ok(_res) = self.backend.load_program(ASM_FILEPATH) {
-                    self.output.clear();
+                match self.backend.load_program_file(ASM_FILEPATH) {
+                    Ok(_) => { self.output.clear(); }
+                    Err(e) => { self.output = e.to_string(); }
                 }
             }
             _ => {}
@@ -113,15 +111,31 @@ impl Application for MipsWindow {
             .push(Text::new(self.backend.display_register(&RegNames::LO)).size(15))
             .push(Text::new(self.backend.display_register(&RegNames::PC)).size(15));
 
+        // Data memory, each word shown as an unsigned int and as a float
+        let mut col_data = Column::new()
+            .align_items(Align::End)
+            .padding(15)
+            .push(Text::new("Data:").size(15));
+        for addr in (memory::DATA_BASE..memory::DATA_BASE + self.backend.data_size()).step_by(4).take(DATA_ROWS) {
+            col_data = col_data.push(Text::new(self.backend.display_memory(addr)).size(15));
+        }
+
         // Fonts
         let terminal_font = Font::External {
             name: "FixedFont",
             bytes: include_bytes!("../src/courier.ttf"),
         };
 
+        // the source line the PC is sitting on
+        let location = match self.backend.current_location() {
+            Some(location) => { location.to_string() }
+            None => { String::new() }
+        };
+
         let display = Column::new()
             .padding(15)
             .width(Length::Fill)
+            .push(Text::new(location).size(15))
             .push(Text::new(&self.output).font(terminal_font));
 
         // All the buttons to run the system
@@ -134,6 +148,7 @@ impl Application for MipsWindow {
         let row = Row::new()
             .align_items(Align::Start)
             .push(col_reg_labels)
+            .push(col_data)
             .push(display)
             .push(buttons);
 
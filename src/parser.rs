@@ -0,0 +1,234 @@
+// Turns the tokens of a line into the statement it holds. A line is any number of
+// `label:` definitions followed by at most one of
+//
+//	NAME = expression				an assignment, before the first segment
+//	.directive operand, operand		a directive
+//	mnemonic operand, operand		an instruction, real or pseudo
+//
+// Operands are separated by commas, or only by spaces when that can't be mistaken
+// for an expression going on, like `add $t0 $t1 $t2`.
+use crate::lexer::{self, Token, TokenKind};
+use crate::mips_int::MipsError;
+use crate::register::RegNames;
+
+/// An expression left as tokens, evaluated once its labels are known
+#[derive(Debug, Clone)]
+pub(crate) struct Expression<'s> {
+	pub(crate) tokens: Vec<Token>,
+	pub(crate) text: &'s str,		// as written, for error messages
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum OperandKind<'s> {
+	Register(usize),
+	Expression(Expression<'s>),				// an immediate, a label, or both
+	Memory(Option<Expression<'s>>, usize),	// `offset($base)`, the offset can be left out
+	String(Vec<u8>),
+	Repeated(Expression<'s>, Expression<'s>),	// `value:count` in a data directive
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Operand<'s> {
+	pub(crate) kind: OperandKind<'s>,
+	pub(crate) text: &'s str,		// as written, which pseudo instructions copy into their expansion
+}
+
+#[derive(Debug)]
+pub(crate) enum Statement<'s> {
+	Assignment(&'s str, Expression<'s>),
+	Directive(&'s str, Vec<Operand<'s>>),
+	Instruction(&'s str, Vec<Operand<'s>>),
+}
+
+impl<'s> Expression<'s> {
+	/// The text of one of the expression's own tokens
+	pub(crate) fn token_text(&self, token: &Token) -> &'s str {
+		let offset = self.tokens[0].start;
+		&self.text[token.start - offset..token.end - offset]
+	}
+}
+
+impl<'s> Operand<'s> {
+	/// The base register of a memory operand as written, like `$sp` in `4($sp)`
+	pub(crate) fn base_register(&self) -> Option<&'s str> {
+		let OperandKind::Memory(_, _) = self.kind else {
+			return None;
+		};
+		let start = self.text.rfind('(')?;
+		let end = self.text.rfind(')')?;
+		Some(self.text[start + 1..end].trim())
+	}
+}
+
+/// Everything on one line of source
+#[derive(Debug)]
+pub(crate) struct Line<'s> {
+	pub(crate) labels: Vec<&'s str>,
+	pub(crate) statement: Option<Statement<'s>>,
+}
+
+/// A register's number from its name, like `$t0`, `$zero` or `$8`
+pub(crate) fn register_index(name: &str) -> Option<usize> {
+	let name = name.strip_prefix('$')?;
+	// plain numbered registers, $0 through $31
+	if let Ok(idx) = name.parse::<usize>() {
+		return if idx < 32 { Some(idx) } else { None };
+	}
+	let name = RegNames::from_name(name)?;
+	RegNames::idx_from_enum(&RegNames::register_align(&name))
+}
+
+/// Whether a token can end an operand, so the one after it may start the next
+fn ends_operand(kind: &TokenKind) -> bool {
+	matches!(kind, TokenKind::Identifier | TokenKind::Register | TokenKind::Integer(_) | TokenKind::Float(_) |
+		TokenKind::String(_) | TokenKind::Char(_) | TokenKind::RightParen)
+}
+
+/// Whether a token can start an operand but never carry on an expression
+fn starts_operand(kind: &TokenKind) -> bool {
+	matches!(kind, TokenKind::Identifier | TokenKind::Register | TokenKind::Integer(_) | TokenKind::Float(_) |
+		TokenKind::String(_) | TokenKind::Char(_) | TokenKind::Operator("~"))
+}
+
+struct Parser<'s> {
+	text: &'s str,
+	tokens: Vec<Token>,
+	line_number: usize,
+}
+
+/// Splits a line into its labels and its statement
+pub(crate) fn parse_line(text: &str, line_number: usize) -> Result<Line<'_>, MipsError> {
+	let parser = Parser {
+		text,
+		tokens: lexer::tokenize(text, line_number)?,
+		line_number,
+	};
+	parser.line()
+}
+
+impl<'s> Parser<'s> {
+	fn text_of(&self, tokens: &[Token]) -> &'s str {
+		match (tokens.first(), tokens.last()) {
+			(Some(first), Some(last)) => { &self.text[first.start..last.end] }
+			_ => { "" }
+		}
+	}
+
+	fn syntax_error(&self) -> MipsError {
+		MipsError::SyntaxError(self.line_number)
+	}
+
+	fn line(&self) -> Result<Line<'s>, MipsError> {
+		let tokens = self.tokens.as_slice();
+		let mut labels = vec![];
+		let mut pos = 0;
+		// numeric local labels are renamed by the preprocessor, but are still names here
+		while let (Some(name), Some(Token { kind: TokenKind::Colon, .. })) = (tokens.get(pos), tokens.get(pos + 1)) {
+			let text = &self.text[name.start..name.end];
+			let is_name = match name.kind {
+				TokenKind::Identifier | TokenKind::Register => { true }
+				TokenKind::Integer(_) => { text.chars().all(|c| c.is_ascii_digit()) }
+				_ => { false }
+			};
+			if !is_name {
+				break;
+			}
+			labels.push(text);
+			pos += 2;
+		}
+
+		let rest = &tokens[pos..];
+		let Some(first) = rest.first() else {
+			return Ok(Line { labels, statement: None });
+		};
+		if first.kind != TokenKind::Identifier {
+			return Err(self.syntax_error());
+		}
+		let name = &self.text[first.start..first.end];
+		let statement = match rest.get(1) {
+			Some(Token { kind: TokenKind::Equals, .. }) => {
+				if rest.len() == 2 {
+					return Err(self.syntax_error());
+				}
+				Statement::Assignment(name, self.expression(&rest[2..]))
+			}
+			_ if name.starts_with('.') => { Statement::Directive(name, self.operands(&rest[1..])?) }
+			_ => { Statement::Instruction(name, self.operands(&rest[1..])?) }
+		};
+		Ok(Line { labels, statement: Some(statement) })
+	}
+
+	fn expression(&self, tokens: &[Token]) -> Expression<'s> {
+		Expression {
+			tokens: tokens.to_vec(),
+			text: self.text_of(tokens),
+		}
+	}
+
+	/// Splits the tokens after a mnemonic or directive into operands
+	fn operands(&self, tokens: &[Token]) -> Result<Vec<Operand<'s>>, MipsError> {
+		let mut operands = vec![];
+		let mut start = 0;
+		let mut depth = 0;
+		for (idx, token) in tokens.iter().enumerate() {
+			match token.kind {
+				TokenKind::LeftParen => { depth += 1; }
+				TokenKind::RightParen => { depth -= 1; }
+				TokenKind::Comma => {
+					operands.push(self.operand(&tokens[start..idx])?);
+					start = idx + 1;
+					continue;
+				}
+				_ => {}
+			}
+			// `add $t0 $t1 $t2`: nothing can follow a register inside an operand,
+			// and two values in a row can't be one expression
+			let previous = if idx > start { Some(&tokens[idx - 1].kind) } else { None };
+			let split = match previous {
+				Some(TokenKind::Register) => { token.kind != TokenKind::RightParen }
+				Some(kind) => { ends_operand(kind) && starts_operand(&token.kind) }
+				None => { false }
+			};
+			if split && depth == 0 {
+				operands.push(self.operand(&tokens[start..idx])?);
+				start = idx;
+			}
+		}
+		if !tokens.is_empty() {
+			operands.push(self.operand(&tokens[start..])?);
+		}
+		Ok(operands)
+	}
+
+	fn operand(&self, tokens: &[Token]) -> Result<Operand<'s>, MipsError> {
+		let text = self.text_of(tokens);
+		let kind = match tokens {
+			[] => { return Err(self.syntax_error()); }
+			[Token { kind: TokenKind::Register, .. }] => { OperandKind::Register(self.register(&tokens[0])?) }
+			[Token { kind: TokenKind::String(bytes), .. }] => { OperandKind::String(bytes.clone()) }
+			[offset @ .., Token { kind: TokenKind::LeftParen, .. }, base @ Token { kind: TokenKind::Register, .. },
+				Token { kind: TokenKind::RightParen, .. }] => {
+				let offset = if offset.is_empty() { None } else { Some(self.expression(offset)) };
+				OperandKind::Memory(offset, self.register(base)?)
+			}
+			_ => {
+				match tokens.iter().position(|t| t.kind == TokenKind::Colon) {
+					Some(colon) if colon > 0 && colon + 1 < tokens.len() => {
+						OperandKind::Repeated(self.expression(&tokens[..colon]), self.expression(&tokens[colon + 1..]))
+					}
+					Some(_) => { return Err(self.syntax_error()); }
+					None => { OperandKind::Expression(self.expression(tokens)) }
+				}
+			}
+		};
+		Ok(Operand { kind, text })
+	}
+
+	fn register(&self, token: &Token) -> Result<usize, MipsError> {
+		let name = &self.text[token.start..token.end];
+		match register_index(name) {
+			Some(idx) => { Ok(idx) }
+			None => { Err(MipsError::UnknownRegister(String::from(name), self.line_number)) }
+		}
+	}
+}
@@ -0,0 +1,433 @@
+// Source text is run through here before the assembler sees it.
+// `.include` pulls in other files, `.eqv` names are replaced with their text,
+// and MARS style macros are expanded in place. Numeric local labels (`1:`, `1b`, `1f`)
+// get a name of their own for every definition. Every line that comes out remembers
+// which file it came from and which macros produced it, so errors can point at
+// both the macro body and the call.
+//
+//	.eqv STACK_WORD 4
+//	.macro push (%reg)
+//		addi $sp, $sp, -STACK_WORD
+//		sw %reg, 0($sp)
+//	.end_macro
+//
+//	push($ra)
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::assembler::{split_labels, strip_comment};
+use crate::mips_int::MipsError;
+
+/// How deep macros may use other macros before we assume they never stop
+const MAX_MACRO_DEPTH: usize = 16;
+
+pub struct SourceLine {
+	pub text: String,					// after `.eqv` names, macro arguments and local labels are replaced
+	pub original: String,				// as it was written
+	pub file: Option<Rc<PathBuf>>,		// None when the source wasn't read from a file
+	pub line_number: usize,				// line in the file, or in the macro body
+	pub macro_stack: Vec<MacroCall>,	// every macro this line came out of, outermost first
+}
+
+#[derive(Clone)]
+pub struct MacroCall {
+	pub name: String,
+	pub file: Option<Rc<PathBuf>>,
+	pub line_number: usize,	// where the macro was used
+}
+
+struct Macro {
+	params: Vec<String>,			// including the leading '%'
+	file: Option<Rc<PathBuf>>,		// where the body was written
+	body: Vec<(usize, String)>,		// line number and text
+	labels: Vec<String>,			// defined in the body, renamed for every use
+}
+
+/// Everything the preprocessor needs to carry between lines and files
+struct Preprocessor {
+	macros: HashMap<(String, usize), Macro>,	// overloaded on the number of arguments
+	expansions: usize,							// gives every use its own label suffix
+	eqvs: HashMap<String, String>,
+	includes: Vec<PathBuf>,						// files being read right now, to catch cycles
+	lines: Vec<SourceLine>,
+}
+
+impl SourceLine {
+	/// Wraps an error with where this line came from: the file it's in,
+	/// then every macro use that produced it, innermost first.
+	pub fn locate(&self, e: MipsError) -> MipsError {
+		let e = in_file(e, &self.file);
+		self.macro_stack.iter().rev().fold(e, |e, call| {
+			let e = MipsError::InMacro(call.name.clone(), call.line_number, Box::new(e));
+			in_file(e, &call.file)
+		})
+	}
+}
+
+fn in_file(e: MipsError, file: &Option<Rc<PathBuf>>) -> MipsError {
+	match file {
+		Some(f) => { MipsError::InFile(f.display().to_string(), Box::new(e)) }
+		None => { e }
+	}
+}
+
+/// Runs the source through includes, `.eqv` and macros.
+///
+/// # Arguments
+///
+/// * `contents` - The program text
+/// * `file` - Where `contents` was read from, if anywhere. Includes are relative to it.
+pub fn preprocess(contents: &str, file: Option<&Path>) -> Result<Vec<SourceLine>, MipsError> {
+	let mut pre = Preprocessor {
+		macros: HashMap::new(),
+		expansions: 0,
+		eqvs: HashMap::new(),
+		includes: vec![],
+		lines: vec![],
+	};
+	let file = file.map(|f| Rc::new(f.to_path_buf()));
+	if let Some(f) = &file {
+		pre.includes.push(canonical(f));
+	}
+	pre.process(contents, &file)?;
+	resolve_local_labels(&mut pre.lines)?;
+	Ok(pre.lines)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+	fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Preprocessor {
+	fn process(&mut self, contents: &str, file: &Option<Rc<PathBuf>>) -> Result<(), MipsError> {
+		let mut source = contents.lines().enumerate();
+		while let Some((idx, line)) = source.next() {
+			let trimmed = strip_comment(line).trim();
+			if let Some(path) = strip_directive(trimmed, ".include") {
+				self.include(path.trim(), file, idx + 1)?;
+				continue;
+			}
+			if let Some(definition) = strip_directive(trimmed, ".eqv") {
+				let definition = definition.trim();
+				let (name, text) = definition.split_once(char::is_whitespace).unwrap_or((definition, ""));
+				if name.is_empty() || !name.chars().all(is_name_char) {
+					return Err(in_file(MipsError::SyntaxError(idx + 1), file));
+				}
+				let text = self.replace_eqvs(text.trim());
+				self.eqvs.insert(String::from(name), text);
+				continue;
+			}
+			let Some(header) = strip_directive(trimmed, ".macro") else {
+				self.expand_line(line, line, file, idx + 1, &[])?;
+				continue;
+			};
+
+			let Some((name, params)) = parse_invocation(header) else {
+				return Err(in_file(MipsError::SyntaxError(idx + 1), file));
+			};
+			if params.iter().any(|p| !p.starts_with('%')) {
+				return Err(in_file(MipsError::SyntaxError(idx + 1), file));
+			}
+
+			let mut body = vec![];
+			loop {
+				let Some((body_idx, body_line)) = source.next() else {
+					// ran off the end of the file looking for .end_macro
+					return Err(in_file(MipsError::SyntaxError(idx + 1), file));
+				};
+				if strip_comment(body_line).trim() == ".end_macro" {
+					break;
+				}
+				body.push((body_idx + 1, String::from(body_line)));
+			}
+			// numeric labels are already local, they don't need renaming
+			let labels = body.iter()
+				.flat_map(|(_, text)| split_labels(text).0)
+				.filter(|name| !is_local_label(name))
+				.map(String::from)
+				.collect();
+			self.macros.insert((name, params.len()), Macro { params, file: file.clone(), body, labels });
+		}
+		Ok(())
+	}
+
+	/// Reads `path`, relative to the including file, and processes it in place
+	fn include(&mut self, path: &str, file: &Option<Rc<PathBuf>>, line_number: usize) -> Result<(), MipsError> {
+		let Some(path) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+			return Err(in_file(MipsError::SyntaxError(line_number), file));
+		};
+		let path = match file.as_ref().and_then(|f| f.parent()) {
+			Some(dir) => { dir.join(path) }
+			None => { PathBuf::from(path) }
+		};
+
+		let key = canonical(&path);
+		if self.includes.contains(&key) {
+			let e = MipsError::IncludeCycle(path.display().to_string(), line_number);
+			return Err(in_file(e, file));
+		}
+		let Ok(contents) = fs::read_to_string(&path) else {
+			let e = MipsError::IncludeNotFound(path.display().to_string(), line_number);
+			return Err(in_file(e, file));
+		};
+
+		self.includes.push(key);
+		self.process(&contents, &Some(Rc::new(path)))?;
+		self.includes.pop();
+		Ok(())
+	}
+
+	fn replace_eqvs(&self, text: &str) -> String {
+		if self.eqvs.is_empty() {
+			return String::from(text);
+		}
+		replace_names(text, |word| self.eqvs.get(word).cloned())
+	}
+
+	fn expand_line(&mut self, text: &str, original: &str, file: &Option<Rc<PathBuf>>, line_number: usize,
+				   stack: &[MacroCall]) -> Result<(), MipsError> {
+		let text = self.replace_eqvs(text);
+		// a macro can be used after labels, which stay on a line of their own
+		let stripped = strip_comment(&text).trim();
+		let rest = split_labels(stripped).1;
+		let invocation = parse_invocation(rest);
+		let Some((name, args)) = invocation.filter(|(n, a)| self.macros.contains_key(&(n.clone(), a.len()))) else {
+			self.lines.push(SourceLine {
+				text,
+				original: String::from(original),
+				file: file.clone(),
+				line_number,
+				macro_stack: stack.to_vec(),
+			});
+			return Ok(());
+		};
+		let labels = &stripped[..stripped.len() - rest.len()];
+		if !labels.is_empty() {
+			self.lines.push(SourceLine {
+				text: String::from(labels),
+				original: String::from(original),
+				file: file.clone(),
+				line_number,
+				macro_stack: stack.to_vec(),
+			});
+		}
+		if stack.len() >= MAX_MACRO_DEPTH {
+			let line = SourceLine {
+				text,
+				original: String::from(original),
+				file: file.clone(),
+				line_number,
+				macro_stack: stack.to_vec(),
+			};
+			return Err(line.locate(MipsError::SyntaxError(line_number)));
+		}
+
+		let mut stack = stack.to_vec();
+		stack.push(MacroCall { name: name.clone(), file: file.clone(), line_number });
+		let id = self.expansions;
+		self.expansions += 1;
+
+		let mac = &self.macros[&(name, args.len())];
+		let body_file = mac.file.clone();
+		let body: Vec<(usize, String, String)> = mac.body.iter()
+			.map(|(n, t)| (*n, substitute(t, mac, &args, id), t.clone()))
+			.collect();
+		for (body_line, body_text, body_original) in body {
+			self.expand_line(&body_text, &body_original, &body_file, body_line, &stack)?;
+		}
+		Ok(())
+	}
+}
+
+/// What follows the directive `name` at the start of `line`, if it is that directive
+/// and not just a longer name that starts the same way
+fn strip_directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+	let rest = line.strip_prefix(name)?;
+	match rest.chars().next() {
+		Some(c) if !c.is_whitespace() => { None }
+		_ => { Some(rest) }
+	}
+}
+
+fn is_name_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+/// Splits `name(a, b)`, `name (a, b)` or `name a, b` into the name and its arguments
+fn parse_invocation(text: &str) -> Option<(String, Vec<String>)> {
+	let text = text.trim();
+	let name_end = text.find(|c: char| !is_name_char(c)).unwrap_or(text.len());
+	if name_end == 0 {
+		return None;
+	}
+	let (name, rest) = text.split_at(name_end);
+	let rest = rest.trim();
+	let rest = match rest.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+		Some(inner) => { inner }
+		None => { rest }
+	};
+	let args = if rest.trim().is_empty() {
+		vec![]
+	} else {
+		split_arguments(rest)
+	};
+	Some((String::from(name), args))
+}
+
+/// Splits on the commas that aren't inside quotes or parentheses,
+/// so `"Hello, world"` and `4($sp)` stay whole
+fn split_arguments(text: &str) -> Vec<String> {
+	let mut args = vec![];
+	let mut start = 0;
+	let mut depth = 0;
+	let mut quote = None;
+	let mut escaped = false;
+	for (idx, c) in text.char_indices() {
+		match quote {
+			Some(q) => {
+				if escaped {
+					escaped = false;
+				} else if c == '\\' {
+					escaped = true;
+				} else if c == q {
+					quote = None;
+				}
+			}
+			None => {
+				match c {
+					'"' | '\'' => { quote = Some(c); }
+					'(' => { depth += 1; }
+					')' => { depth -= 1; }
+					',' if depth == 0 => {
+						args.push(String::from(text[start..idx].trim()));
+						start = idx + 1;
+					}
+					_ => {}
+				}
+			}
+		}
+	}
+	args.push(String::from(text[start..].trim()));
+	args
+}
+
+fn is_local_label(name: &str) -> bool {
+	!name.is_empty() && name.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The name the `index`th definition of numeric label `name` is given
+fn local_label_name(name: &str, index: usize) -> String {
+	format!(".L{}.{}", name, index)
+}
+
+/// Whether `name` was made up for a numeric label, rather than written in the source
+pub(crate) fn is_renamed_local_label(name: &str) -> bool {
+	name.starts_with(".L")
+}
+
+/// Numeric labels like `1:` can be defined any number of times.
+/// `1b` refers to the closest `1:` above (or on) its line, and `1f` to the closest one below.
+fn resolve_local_labels(lines: &mut [SourceLine]) -> Result<(), MipsError> {
+	// every line each numeric label is defined on, in order
+	let mut definitions: HashMap<String, Vec<usize>> = HashMap::new();
+	for (idx, line) in lines.iter().enumerate() {
+		for name in split_labels(&line.text).0 {
+			if is_local_label(name) {
+				definitions.entry(String::from(name)).or_insert_with(Vec::new).push(idx);
+			}
+		}
+	}
+
+	for (idx, line) in lines.iter_mut().enumerate() {
+		let rest_len = split_labels(&line.text).1.len();
+		let (prefix, rest) = line.text.split_at(line.text.len() - rest_len);
+		let prefix = replace_names(prefix, |word| {
+			let defs = definitions.get(word)?;
+			Some(local_label_name(word, defs.iter().position(|&d| d == idx)?))
+		});
+
+		let mut undefined = None;
+		let rest = replace_names(rest, |word| {
+			let (name, backward) = match (word.strip_suffix('b'), word.strip_suffix('f')) {
+				(Some(name), _) => { (name, true) }
+				(_, Some(name)) => { (name, false) }
+				_ => { return None; }
+			};
+			if !is_local_label(name) {
+				return None;
+			}
+			let defs = definitions.get(name).map(|d| d.as_slice()).unwrap_or(&[]);
+			let index = if backward {
+				defs.iter().rposition(|&d| d <= idx)
+			} else {
+				defs.iter().position(|&d| d > idx)
+			};
+			if index.is_none() {
+				undefined.get_or_insert_with(|| String::from(word));
+			}
+			Some(local_label_name(name, index?))
+		});
+		if let Some(word) = undefined {
+			let e = MipsError::UndefinedLabel(word, line.line_number);
+			return Err(line.locate(e));
+		}
+		line.text = prefix + &rest;
+	}
+	Ok(())
+}
+
+/// Calls `replace` on every name (or `%param`) in the line, swapping in whatever it returns.
+/// Nothing inside string or character quotes is touched.
+fn replace_names<F: FnMut(&str) -> Option<String>>(text: &str, mut replace: F) -> String {
+	let chars: Vec<char> = text.chars().collect();
+	let mut out = String::with_capacity(text.len());
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c == '"' || c == '\'' {
+			out.push(c);
+			i += 1;
+			while i < chars.len() {
+				out.push(chars[i]);
+				i += 1;
+				if chars[i - 1] == '\\' && i < chars.len() {
+					out.push(chars[i]);
+					i += 1;
+				} else if chars[i - 1] == c {
+					break;
+				}
+			}
+		} else if c == '%' || is_name_char(c) {
+			let start = i;
+			i += 1;
+			while i < chars.len() && is_name_char(chars[i]) {
+				i += 1;
+			}
+			let word: String = chars[start..i].iter().collect();
+			match replace(&word) {
+				Some(new) => { out.push_str(&new); }
+				None => { out.push_str(&word); }
+			}
+		} else {
+			out.push(c);
+			i += 1;
+		}
+	}
+	out
+}
+
+/// Fills the arguments into one line of a macro body, and gives the labels
+/// defined in the body a suffix unique to this use so it can be used twice.
+fn substitute(text: &str, mac: &Macro, args: &[String], id: usize) -> String {
+	replace_names(text, |word| {
+		if let Some(idx) = mac.params.iter().position(|p| p == word) {
+			Some(args[idx].clone())
+		} else if mac.labels.iter().any(|l| l == word) {
+			Some(format!("{}_M{}", word, id))
+		} else {
+			None
+		}
+	})
+}
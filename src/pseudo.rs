@@ -0,0 +1,195 @@
+// Pseudo instructions are shorthands that are not part of the MIPS instruction set.
+// The assembler rewrites each one into the real instructions it stands for,
+// following what MARS does and using $at (R1) as the scratch register.
+use crate::expression;
+use crate::assembler::LineContext;
+use crate::parser::{Expression, Operand, OperandKind};
+use crate::mips_int::MipsError;
+
+pub fn is_pseudo(op: &str) -> bool {
+	matches!(op,
+		"li" | "la" | "move" |
+		"blt" | "bgt" | "ble" | "bge" | "beqz" | "bnez" | "b" |
+		"neg" | "not" | "nop" | "mul" | "rem" | "abs")
+}
+
+/// Rewrites a pseudo instruction into lines of real instructions.
+///
+/// How many lines come back only depends on how the operands are written,
+/// never on the value of a label, so both assembler passes agree on the size.
+///
+/// # Arguments
+///
+/// * `op` - The pseudo instruction's name
+/// * `operands` - The operands the instruction was written with
+/// * `ctx` - Constants and labels visible to this line
+pub(crate) fn expand(op: &str, operands: &[Operand], ctx: &LineContext) -> Result<Vec<String>, MipsError> {
+	let term = |idx: usize| -> Result<&Operand, MipsError> {
+		match operands.get(idx) {
+			Some(t) => { Ok(t) }
+			None => { Err(MipsError::SyntaxError(ctx.line_number)) }
+		}
+	};
+	let operand = |idx: usize| -> Result<&str, MipsError> { Ok(term(idx)?.text) };
+
+	let lines = match op {
+		"li" => { expand_li(operand(0)?, term(1)?, ctx)? }
+		"la" => { expand_la(operand(0)?, term(1)?, ctx)? }
+		"move" => { vec![format!("addu {}, $zero, {}", operand(0)?, operand(1)?)] }
+		// branches compare into $at, then test it against $zero
+		"blt" => { branch_on_less(term(0)?, term(1)?, "bne", operand(2)?) }
+		"bgt" => { branch_on_less(term(1)?, term(0)?, "bne", operand(2)?) }
+		"ble" => { branch_on_less(term(1)?, term(0)?, "beq", operand(2)?) }
+		"bge" => { branch_on_less(term(0)?, term(1)?, "beq", operand(2)?) }
+		"beqz" => { vec![format!("beq {}, $zero, {}", operand(0)?, operand(1)?)] }
+		"bnez" => { vec![format!("bne {}, $zero, {}", operand(0)?, operand(1)?)] }
+		"b" => { vec![format!("beq $zero, $zero, {}", operand(0)?)] }
+		// arithmetic
+		"neg" => { vec![format!("sub {}, $zero, {}", operand(0)?, operand(1)?)] }
+		"not" => { vec![format!("nor {}, {}, $zero", operand(0)?, operand(1)?)] }
+		"nop" => { vec![String::from("sll $zero, $zero, 0")] }
+		"mul" => {
+			vec![
+				format!("mult {}, {}", operand(1)?, operand(2)?),
+				format!("mflo {}", operand(0)?),
+			]
+		}
+		"rem" => {
+			vec![
+				format!("div {}, {}", operand(1)?, operand(2)?),
+				format!("mfhi {}", operand(0)?),
+			]
+		}
+		"abs" => {
+			// the sign bit smeared across $at is either all 0s or all 1s,
+			// xor and subtract then flip negative numbers around
+			let (dest, src) = (operand(0)?, operand(1)?);
+			vec![
+				format!("sra $at, {}, 31", src),
+				format!("xor {}, $at, {}", dest, src),
+				format!("subu {}, {}, $at", dest, dest),
+			]
+		}
+		_ => { return Err(MipsError::UnknownMnemonic(String::from(op), ctx.line_number)); }
+	};
+	Ok(lines)
+}
+
+/// `li` picks the shortest sequence that can hold the value.
+/// Anything that uses a label is treated the same as `la`.
+fn expand_li(dest: &str, operand: &Operand, ctx: &LineContext) -> Result<Vec<String>, MipsError> {
+	let value = match expression::evaluate_constant(immediate(operand, ctx)?, ctx) {
+		Ok(v) => { v }
+		Err(MipsError::UndefinedLabel(_, _)) => { return expand_la(dest, operand, ctx); }
+		Err(e) => { return Err(e); }
+	};
+	if value < i32::MIN as i64 || value > u32::MAX as i64 {
+		return Err(MipsError::ImmediateOutOfRange(value, ctx.line_number));
+	}
+
+	if value >= i16::MIN as i64 && value <= i16::MAX as i64 {
+		Ok(vec![format!("addiu {}, $zero, {}", dest, value)])
+	} else if value >= 0 && value <= u16::MAX as i64 {
+		Ok(vec![format!("ori {}, $zero, {}", dest, value)])
+	} else {
+		let value = value as u32;
+		Ok(vec![
+			format!("lui $at, {}", value >> 16),
+			format!("ori {}, $at, {}", dest, value & 0xFFFF),
+		])
+	}
+}
+
+/// `la` always takes two instructions, even when the address would fit
+/// in 16 bits, since the label might not be known yet.
+/// The `offset($reg)` form is just an add.
+fn expand_la(dest: &str, operand: &Operand, ctx: &LineContext) -> Result<Vec<String>, MipsError> {
+	if let OperandKind::Memory(offset, _) = &operand.kind {
+		let reg = operand.base_register().unwrap_or("$zero");
+		let Some(offset) = offset else {
+			return Ok(vec![format!("addiu {}, {}, 0", dest, reg)]);
+		};
+		// a small constant fits the immediate, a label or anything wider is added on
+		match expression::evaluate_constant(offset, ctx) {
+			Ok(v) if v >= i16::MIN as i64 && v <= i16::MAX as i64 => {
+				return Ok(vec![format!("addiu {}, {}, {}", dest, reg, v)]);
+			}
+			Ok(_) | Err(MipsError::UndefinedLabel(_, _)) => {}
+			Err(e) => { return Err(e); }
+		}
+		let addr = expression::evaluate(offset, ctx)? as u32;
+		return Ok(vec![
+			format!("lui $at, {}", addr >> 16),
+			format!("ori $at, $at, {}", addr & 0xFFFF),
+			format!("addu {}, $at, {}", dest, reg),
+		]);
+	}
+
+	let addr = expression::evaluate(immediate(operand, ctx)?, ctx)? as u32;
+	Ok(vec![
+		format!("lui $at, {}", addr >> 16),
+		format!("ori {}, $at, {}", dest, addr & 0xFFFF),
+	])
+}
+
+/// Loads and stores take their address as a 16 bit offset from a register.
+/// When the operand is a label, or a constant too wide for that,
+/// the upper half goes into $at first and the lower half stays as the offset.
+/// Returns `None` when the instruction can be encoded as it is.
+///
+/// Like `li`, the size depends on whether the operand is a constant,
+/// never on the value of a label.
+pub(crate) fn expand_memory_operand(op: &str, operands: &[Operand], ctx: &LineContext) -> Result<Option<Vec<String>>, MipsError> {
+	if !matches!(op, "lw" | "lh" | "lhu" | "lb" | "lbu" | "sw" | "sh" | "sb") {
+		return Ok(None);
+	}
+	let [reg, operand] = operands else {
+		return Err(MipsError::SyntaxError(ctx.line_number));
+	};
+	let (offset, base) = match &operand.kind {
+		OperandKind::Memory(Some(offset), _) => { (offset, operand.base_register()) }
+		OperandKind::Expression(offset) => { (offset, None) }
+		_ => { return Ok(None); }
+	};
+	match expression::evaluate_constant(offset, ctx) {
+		Ok(v) if v >= i16::MIN as i64 && v <= i16::MAX as i64 => { return Ok(None); }
+		Ok(_) | Err(MipsError::UndefinedLabel(_, _)) => {}
+		Err(e) => { return Err(e); }
+	}
+
+	// the offset is sign extended, so the upper half makes up for a negative lower half
+	let addr = expression::evaluate(offset, ctx)? as u32;
+	let low = addr as u16 as i16;
+	let mut lines = vec![format!("lui $at, {}", addr.wrapping_sub(low as u32) >> 16)];
+	if let Some(base) = base {
+		lines.push(format!("addu $at, $at, {}", base));
+	}
+	lines.push(format!("{} {}, {}($at)", op, reg.text, low));
+	Ok(Some(lines))
+}
+
+/// Sets $at when `lhs < rhs`, then branches with `branch` to `label`.
+/// Either side may be an immediate instead of a register.
+fn branch_on_less(lhs: &Operand, rhs: &Operand, branch: &str, label: &str) -> Vec<String> {
+	let is_register = |o: &Operand| matches!(o.kind, OperandKind::Register(_));
+	let (lhs_text, rhs_text) = (lhs.text, rhs.text);
+	let mut lines = vec![];
+	if !is_register(rhs) {
+		lines.push(format!("slti $at, {}, {}", lhs_text, rhs_text));
+	} else if !is_register(lhs) {
+		lines.push(format!("addi $at, $zero, {}", lhs_text));
+		lines.push(format!("slt $at, $at, {}", rhs_text));
+	} else {
+		lines.push(format!("slt $at, {}, {}", lhs_text, rhs_text));
+	}
+	lines.push(format!("{} $at, $zero, {}", branch, label));
+	lines
+}
+
+/// The expression an immediate operand was written as
+fn immediate<'a, 's>(operand: &'a Operand<'s>, ctx: &LineContext) -> Result<&'a Expression<'s>, MipsError> {
+	match &operand.kind {
+		OperandKind::Expression(expr) => { Ok(expr) }
+		_ => { Err(MipsError::SyntaxError(ctx.line_number)) }
+	}
+}
@@ -73,8 +73,14 @@ pub enum RegNames {
 
 impl RegNames {
 	pub fn str_to_enum(s: &str) -> RegNames {
+		RegNames::from_name(s).unwrap_or(RegNames::ZERO)
+	}
+
+	/// Looks up a register by name, without the leading '$'
+	pub fn from_name(s: &str) -> Option<RegNames> {
 		let lower = s.to_ascii_uppercase();
-		match lower.as_str() {
+		let reg = match lower.as_str() {
+			"R0" => RegNames::R0,
 			"R1" => RegNames::R1,
 			"R2" => RegNames::R2,
 			"R3" => RegNames::R3,
@@ -107,6 +113,15 @@ impl RegNames {
 			"R30" => RegNames::R30,
 			"R31" => RegNames::R31,
 			// now for the special names
+			"ZERO" => RegNames::ZERO,
+			"AT" => RegNames::At,
+			"V0" => RegNames::V0,
+			"V1" => RegNames::V1,
+			"A0" => RegNames::A0,
+			"A1" => RegNames::A1,
+			"A2" => RegNames::A2,
+			"A3" => RegNames::A3,
+
 			"T0" => RegNames::T0,
 			"T1" => RegNames::T1,
 			"T2" => RegNames::T2,
@@ -127,12 +142,20 @@ impl RegNames {
 			"S6" => RegNames::S6,
 			"S7" => RegNames::S7,
 
+			"K0" => RegNames::K0,
+			"K1" => RegNames::K1,
+			"GP" => RegNames::GP,
+			"SP" => RegNames::SP,
+			"FP" => RegNames::FP,
+			"RA" => RegNames::RA,
+
 			"HI" => RegNames::HI,
 			"LO" => RegNames::LO,
 			"PC" => RegNames::PC,
 
-			_ => RegNames::ZERO
-		}
+			_ => { return None; }
+		};
+		Some(reg)
 	}
 
 	pub fn to_str(n: &RegNames) -> String {
@@ -258,11 +281,21 @@ impl RegNames {
 			RegNames::R29 => Some(29),
 			RegNames::R30 => Some(30),
 			RegNames::R31 => Some(31),
+			// not general purpose registers
+			RegNames::PC | RegNames::HI | RegNames::LO => None,
 			_ => RegNames::idx_from_enum( &RegNames::register_align(n) )
 		}
 	}
 }
 
+/// The names R0 through R31 go by in assembly, without the leading '$'
+pub const ABI_NAMES: [&str; 32] = [
+	"zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+	"t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+	"s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+	"t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
 #[derive(Copy, Clone)]
 pub union RegisterValue {
 	pub uint: u32,
@@ -299,9 +332,10 @@ impl Register {
 		self.value.uint = val;
 	}
 
+	/// Adds to the value, wrapping around like the hardware does
 	pub fn add_u32(&mut self, val: u32) {
 		unsafe {
-			self.value.uint += val;
+			self.value.uint = self.value.uint.wrapping_add(val);
 		}
 	}
 
@@ -0,0 +1,62 @@
+// The assembler leaves behind a map from every address it filled back to the source
+// that filled it. Runtime errors, the GUI and tracing use it to show the line behind an address.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Where a piece of the program was written
+#[derive(Clone)]
+pub struct SourceLocation {
+	pub file: Option<Rc<PathBuf>>,	// None when the source wasn't read from a file
+	pub line: usize,				// 1 based
+	pub column: usize,				// 1 based, where the statement starts
+	pub text: String,				// the line as it was written
+	pub label: Option<String>,		// the closest label above the line
+}
+
+#[derive(Clone)]
+pub struct SourceMap {
+	ranges: BTreeMap<u32, (u32, SourceLocation)>,	// start address to size and location
+}
+
+impl fmt::Display for SourceLocation {
+	/// `file:line:column`, the enclosing label if there is one, then the source text
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let Some(file) = &self.file {
+			write!(f, "{}:", file.display())?;
+		}
+		write!(f, "{}:{}", self.line, self.column)?;
+		if let Some(label) = &self.label {
+			write!(f, " in {}", label)?;
+		}
+		write!(f, ": {}", self.text.trim())
+	}
+}
+
+impl Default for SourceMap {
+	fn default() -> SourceMap {
+		SourceMap::new()
+	}
+}
+
+impl SourceMap {
+	pub fn new() -> SourceMap {
+		SourceMap {
+			ranges: BTreeMap::new(),
+		}
+	}
+
+	/// Records that `size` bytes starting at `address` came from `location`
+	pub fn add(&mut self, address: u32, size: u32, location: SourceLocation) {
+		if size > 0 {
+			self.ranges.insert(address, (size, location));
+		}
+	}
+
+	/// The source behind any byte address the assembler filled
+	pub fn lookup(&self, address: u32) -> Option<&SourceLocation> {
+		let (start, (size, location)) = self.ranges.range(..=address).next_back()?;
+		if address - start < *size { Some(location) } else { None }
+	}
+}
@@ -1,15 +1,64 @@
 #[cfg(test)]
 mod tests {
 	use crate::mips_int;
+	use crate::mips_int::{MipsError, StopReason};
+	use crate::diagnostic::{Diagnostic, Severity};
+	use crate::memory::{self, Memory};
+	use crate::register::{self, RegNames};
+	use crate::instruction::{self, DecodedInstruction, OPName};
+	use crate::disassembler::{self, Disassembler};
+	use crate::image::{Image, Segment};
+	use crate::elf;
+	use crate::assembler::Assembler;
+	use crate::lexer::{self, TokenKind};
+	use crate::parser::{self, OperandKind, Statement};
+
+	fn program_words(intr: &mips_int::MipsInterpreter) -> Vec<u32> {
+		intr.get_program_contents()
+			.lines()
+			.map(|l| l.parse().unwrap())
+			.collect()
+	}
+
+	/// Everything a failed load reported
+	fn load_errors(result: Result<(), MipsError>) -> Vec<Diagnostic> {
+		match result {
+			Err(MipsError::Diagnostics(d)) => { d }
+			_ => panic!("expected the load to fail"),
+		}
+	}
+
+	fn first_error(result: Result<(), MipsError>) -> MipsError {
+		load_errors(result).remove(0).error
+	}
+
+	/// Loads `code` after a little data and runs it until the PC passes the last instruction
+	fn execute(code: &str) -> mips_int::MipsInterpreter {
+		let data = format!(".data\nvalue: .word 0x8081fffe, 7\nmsg: .asciiz \"hi\"\n.text\nmain:\n{}", code);
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(&data).is_ok(), "{}", code);
+		let end = memory::TEXT_BASE + 4 * intr.memory().text().len() as u32;
+		for _ in 0..100 {
+			if intr.get_register(&RegNames::PC) >= end {
+				break;
+			}
+			intr.process_line().unwrap();
+		}
+		intr
+	}
 
 	#[test]
 	fn test_space10() {
+		// 10 BYTES, so 2.5 lines, round up to 3 lines of 0.
 		let data =
 			".data\n\
 			board:\n\
-			.space 10"; // 10 BYTES, so 2.5 lines, round up to 3 lines of 0.
+			.space 10\n\
+			.text\n\
+			main:";
 		let mut intr = mips_int::MipsInterpreter::new();
-		intr.load_program(data);
+		// a program without `main` no longer loads at all
+		assert!(intr.load_program(data).is_ok());
 		let code = intr.get_program_contents();
 		let mut count = 0;
 		for line in code.lines() {
@@ -17,16 +66,24 @@ mod tests {
 			count += 1;
 		}
 		assert_eq!(count, 3);
+
+		// without `main` the load fails and leaves nothing behind
+		assert!(matches!(first_error(intr.load_program(".data\nboard:\n.space 10")), MipsError::MissingMain));
+		assert_eq!(intr.get_program_contents(), "");
 	}
 
 	#[test]
 	fn test_space12() {
+		// 12 BYTES, so 3 lines
 		let data =
 			".data\n\
 			board:\n\
-			.space 12"; // 12 BYTES, so 3 lines
+			.space 12\n\
+			.text\n\
+			main:";
 		let mut intr = mips_int::MipsInterpreter::new();
-		intr.load_program(data);
+		// a program without `main` no longer loads at all
+		assert!(intr.load_program(data).is_ok());
 		let code = intr.get_program_contents();
 		let mut count = 0;
 		for line in code.lines() {
@@ -35,4 +92,1547 @@ mod tests {
 		}
 		assert_eq!(count, 3);
 	}
+
+	#[test]
+	fn test_space_limits() {
+		// the static data can fill up to where the heap starts, and no further
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(".data\n.space 0x30000\n.text\nmain:").is_ok());
+		assert_eq!(intr.memory().static_size(), 0x30000);
+		for (data, value) in [
+			(".data\n.space -1", -1),
+			(".data\n.space 0x30001", 0x30001),
+			(".data\n.space 0x7fffffff", 0x7fffffff),
+			(".data\n.word 0:0x7fffffff", 0x7fffffff),
+			(".data\n.word 0:-1", -1),
+			(".data\n.word 0:0xC001", 0xC001),
+			(".data\n.double 0:0x6001", 0x6001),
+		] {
+			let error = first_error(intr.load_program(data));
+			assert!(matches!(error, MipsError::ImmediateOutOfRange(v, 2) if v == value), "{}: {:?}", data, error);
+		}
+		// sizes have to be known in the first pass
+		let error = first_error(intr.load_program(".data\n.space end\nend:"));
+		assert!(matches!(error, MipsError::UndefinedLabel(ref l, 2) if l == "end"), "{:?}", error);
+	}
+
+	#[test]
+	fn test_forward_labels() {
+		let data =
+			".data\n\
+			.text\n\
+			main:\n\
+			beq $t0, $t1, done\n\
+			j done\n\
+			done:\n\
+			jal main\n\
+			bne $t0, $zero, done";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(code, vec![
+			0x11090001,	// beq $t0, $t1, +1
+			0x08100002,	// j 0x00400008
+			0x0C100000,	// jal 0x00400000
+			0x1500FFFE,	// bne $t0, $zero, -2
+		]);
+	}
+
+	#[test]
+	fn test_undefined_label() {
+		let data =
+			".data\n\
+			.text\n\
+			main:\n\
+			j nowhere";
+		let mut intr = mips_int::MipsInterpreter::new();
+		match first_error(intr.load_program(data)) {
+			MipsError::UndefinedLabel(lbl, line) => {
+				assert_eq!(lbl, "nowhere");
+				assert_eq!(line, 4);
+			}
+			_ => panic!("expected an undefined label error"),
+		}
+	}
+
+	#[test]
+	fn test_immediates() {
+		let data =
+			"LIMIT = 10\n\
+			.data\n\
+			.text\n\
+			main:\n\
+			addi $sp, $sp, -28\n\
+			ori $t0, $zero, 0xFFFF\n\
+			andi $t1, $t0, 0b1010\n\
+			slti $t0, $a0, LIMIT\n\
+			lui $at, 0x1001";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(code, vec![
+			0x23BDFFE4,	// addi $sp, $sp, -28
+			0x3408FFFF,	// ori $t0, $zero, 0xFFFF
+			0x3109000A,	// andi $t1, $t0, 10
+			0x2888000A,	// slti $t0, $a0, 10
+			0x3C011001,	// lui $at, 0x1001
+		]);
+	}
+
+	#[test]
+	fn test_immediate_ranges() {
+		let sign_extended =
+			".data\n\
+			.text\n\
+			main:\n\
+			addiu $t0, $t0, 40000";
+		let mut intr = mips_int::MipsInterpreter::new();
+		match first_error(intr.load_program(sign_extended)) {
+			MipsError::ImmediateOutOfRange(v, line) => {
+				assert_eq!(v, 40000);
+				assert_eq!(line, 4);
+			}
+			_ => panic!("expected an out of range immediate"),
+		}
+
+		let zero_extended =
+			".data\n\
+			.text\n\
+			main:\n\
+			xori $t0, $t0, -1";
+		assert!(matches!(first_error(intr.load_program(zero_extended)), MipsError::ImmediateOutOfRange(-1, 4)));
+	}
+
+	#[test]
+	fn test_memory_operands() {
+		let data =
+			".data\n\
+			value:\n\
+			.word 7\n\
+			buf:\n\
+			.space 8\n\
+			.text\n\
+			main:\n\
+			sw $ra, 24($sp)\n\
+			lw $t0, ($a0)\n\
+			lb $t1, -1($t0)\n\
+			lw $t0, value\n\
+			sh $t0, buf+2\n\
+			lbu $t1, buf($t2)\n\
+			lw $t0, 0x1234FFFC";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(&code[3..], &[
+			0xAFBF0018,	// sw $ra, 24($sp)
+			0x8C880000,	// lw $t0, 0($a0)
+			0x8109FFFF,	// lb $t1, -1($t0)
+			0x3C011001,	// lui $at, 0x1001
+			0x8C280000,	// lw $t0, 0($at)
+			0x3C011001,	// lui $at, 0x1001
+			0xA4280006,	// sh $t0, 6($at)
+			0x3C011001,	// lui $at, 0x1001
+			0x002A0821,	// addu $at, $at, $t2
+			0x90290004,	// lbu $t1, 4($at)
+			0x3C011235,	// lui $at, 0x1235
+			0x8C28FFFC,	// lw $t0, -4($at)
+		]);
+	}
+
+	#[test]
+	fn test_pseudo_sizes() {
+		let data =
+			".data\n\
+			.text\n\
+			main:\n\
+			li $t0, 5\n\
+			li $t1, 0xFFFF\n\
+			li $t2, 0x12345678\n\
+			la $a0, main\n\
+			move $a1, $t0\n\
+			blt $t0, $t1, main\n\
+			j end\n\
+			nop\n\
+			end:\n\
+			b end";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(code, vec![
+			0x24080005,	// addiu $t0, $zero, 5
+			0x3409FFFF,	// ori $t1, $zero, 0xFFFF
+			0x3C011234,	// lui $at, 0x1234
+			0x342A5678,	// ori $t2, $at, 0x5678
+			0x3C010040,	// lui $at, 0x0040
+			0x34240000,	// ori $a0, $at, 0
+			0x00082821,	// addu $a1, $zero, $t0
+			0x0109082A,	// slt $at, $t0, $t1
+			0x1420FFF7,	// bne $at, $zero, -9
+			0x0810000B,	// j 0x0040002C
+			0x00000000,	// sll $zero, $zero, 0
+			0x1000FFFF,	// beq $zero, $zero, -1
+		]);
+
+		// a label plus a register takes the whole address through $at
+		let intr = execute("li $t1, 4\nla $t0, value($t1)\nlw $t2, 0($t0)\nla $t3, 2($t1)");
+		assert_eq!(&intr.memory().text()[1..6], [
+			0x3C011001,	// lui $at, 0x1001
+			0x34210000,	// ori $at, $at, 0
+			0x00294021,	// addu $t0, $at, $t1
+			0x8D0A0000,	// lw $t2, 0($t0)
+			0x252B0002,	// addiu $t3, $t1, 2
+		]);
+		assert_eq!(intr.get_register(&RegNames::T0), memory::DATA_BASE + 4);
+		assert_eq!(intr.get_register(&RegNames::T2), 7);
+		assert_eq!(intr.get_register(&RegNames::T3), 6);
+	}
+
+	#[test]
+	fn test_pseudo_arithmetic() {
+		let data =
+			".data\n\
+			.text\n\
+			main:\n\
+			mul $v0, $t0, $t1\n\
+			abs $v1, $a0\n\
+			neg $t0, $t1\n\
+			not $t0, $t1";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(code, vec![
+			0x01090018,	// mult $t0, $t1
+			0x00001012,	// mflo $v0
+			0x00040FC3,	// sra $at, $a0, 31
+			0x00241826,	// xor $v1, $at, $a0
+			0x00611823,	// subu $v1, $v1, $at
+			0x00094022,	// sub $t0, $zero, $t1
+			0x01204027,	// nor $t0, $t1, $zero
+		]);
+	}
+
+	#[test]
+	fn test_constant_expressions() {
+		let data =
+			"NUM_ROWS = 10\n\
+			NUM_COLUMNS = 0xA\n\
+			GAME_BOARD_SIZE = NUM_ROWS * NUM_COLUMNS	#100\n\
+			MASK = ~(1 << 4) & 0xFF\n\
+			.data\n\
+			board:\n\
+			.space GAME_BOARD_SIZE / 25\n\
+			table:\n\
+			.word MASK + table\n\
+			.text\n\
+			main:\n\
+			addi $t0, $t0, -(NUM_ROWS << 1)\n\
+			lw $t1, (NUM_COLUMNS - 2) * 4($sp)\n\
+			li $v0, GAME_BOARD_SIZE % 7";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(code, vec![
+			0,			// board
+			0x100100F3,	// table, 0xEF past 0x10010004
+			0x2108FFEC,	// addi $t0, $t0, -20
+			0x8FA90020,	// lw $t1, 32($sp)
+			0x24020002,	// addiu $v0, $zero, 2
+		]);
+	}
+
+	#[test]
+	fn test_invalid_expression() {
+		let data =
+			"X = 5 / 0\n\
+			.data\n\
+			.text\n\
+			main:";
+		let mut intr = mips_int::MipsInterpreter::new();
+		match first_error(intr.load_program(data)) {
+			MipsError::InvalidExpression(e, line) => {
+				assert_eq!(e, "5 / 0");
+				assert_eq!(line, 1);
+			}
+			_ => panic!("expected an invalid expression"),
+		}
+	}
+
+	#[test]
+	fn test_macros() {
+		let data =
+			".macro push (%reg)\n\
+			addi $sp, $sp, -4\n\
+			sw %reg, 0($sp)\n\
+			.end_macro\n\
+			.macro pop (%reg)\n\
+			lw %reg, 0($sp)\n\
+			addi $sp, $sp, 4\n\
+			.end_macro\n\
+			.macro spin (%reg)\n\
+			loop:\n\
+			addi %reg, %reg, -1\n\
+			bne %reg, $zero, loop\n\
+			.end_macro\n\
+			.macro spin (%reg, %count)\n\
+			li %reg, %count\n\
+			spin (%reg)\n\
+			.end_macro\n\
+			.data\n\
+			.text\n\
+			main:\n\
+			push($ra)\n\
+			pop $ra\n\
+			spin($t0)\n\
+			spin($t1, 3)";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let code = program_words(&intr);
+		assert_eq!(code, vec![
+			0x23BDFFFC,	// addi $sp, $sp, -4
+			0xAFBF0000,	// sw $ra, 0($sp)
+			0x8FBF0000,	// lw $ra, 0($sp)
+			0x23BD0004,	// addi $sp, $sp, 4
+			0x2108FFFF,	// addi $t0, $t0, -1
+			0x1500FFFE,	// bne $t0, $zero, -2
+			0x24090003,	// addiu $t1, $zero, 3
+			0x2129FFFF,	// addi $t1, $t1, -1
+			0x1520FFFE,	// bne $t1, $zero, -2
+		]);
+
+		// commas in quotes don't split arguments, labels can come before a call,
+		// and labels in a body aren't renamed inside character quotes
+		let data =
+			".macro print_str (%str)\n\
+			.data\n\
+			s: .asciiz %str\n\
+			.text\n\
+			la $a0, s\n\
+			li $v0, 4\n\
+			syscall\n\
+			li $a0, 's'\n\
+			.end_macro\n\
+			.macro inc (%reg)\n\
+			addi %reg, %reg, 1\n\
+			.end_macro\n\
+			.text\n\
+			main:\n\
+			print_str (\"Hello, world\")\n\
+			loop: inc($t0)\n\
+			blt $t0, 3, loop\n\
+			li $v0, 10\n\
+			syscall";
+		assert!(intr.load_program(data).is_ok());
+		assert!(matches!(intr.run(100), StopReason::Exited(0)));
+		assert_eq!(intr.output(), "Hello, world");
+		assert_eq!(intr.get_register(&RegNames::A0), 's' as u32);
+		assert_eq!(intr.get_register(&RegNames::T0), 3);
+	}
+
+	#[test]
+	fn test_macro_error_location() {
+		let data =
+			".macro bad\n\
+			frob $t0\n\
+			.end_macro\n\
+			.data\n\
+			.text\n\
+			main:\n\
+			bad";
+		let mut intr = mips_int::MipsInterpreter::new();
+		let errors = load_errors(intr.load_program(data));
+		assert!(matches!(errors[0].error, MipsError::UnknownMnemonic(ref op, 2) if op == "frob"));
+		assert_eq!(errors[0].notes, vec!["in macro `bad` used at 7"]);
+	}
+
+	#[test]
+	fn test_eqv() {
+		let data =
+			".eqv COUNTER $t0\n\
+			.eqv STEP 2\n\
+			.data\n\
+			.text\n\
+			main:\n\
+			addi COUNTER, COUNTER, STEP\n\
+			li $t1, 'x'";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(&format!(".eqv x 9\n{}", data)).is_ok());
+		// names aren't replaced inside character quotes
+		assert_eq!(program_words(&intr), vec![0x21080002, 0x24090078]);
+	}
+
+	#[test]
+	fn test_include() {
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program_file("data/tests/include_main.asm").is_ok());
+		assert_eq!(program_words(&intr), vec![
+			0x24020001,	// addiu $v0, $zero, 1
+			0x23BDFFFC,	// addi $sp, $sp, -4
+		]);
+	}
+
+	#[test]
+	fn test_include_errors() {
+		let mut intr = mips_int::MipsInterpreter::new();
+		let errors = load_errors(intr.load_program_file("data/tests/include_error.asm"));
+		let span = errors[0].span.as_ref().unwrap();
+		assert_eq!(span.file.as_deref(), Some("data/tests/include_defs.asm"));
+		assert!(matches!(errors[0].error, MipsError::UnknownMnemonic(ref op, 3) if op == "PRINT_INT"));
+
+		let errors = load_errors(intr.load_program_file("data/tests/cycle_a.asm"));
+		let span = errors[0].span.as_ref().unwrap();
+		assert_eq!(span.file.as_deref(), Some("data/tests/cycle_b.asm"));
+		assert!(matches!(errors[0].error, MipsError::IncludeCycle(ref f, 1) if f == "data/tests/cycle_a.asm"));
+
+		// longer names that start like a directive aren't taken for it
+		let errors = load_errors(intr.load_program(".text\nmain:\n.includefoo \"x.asm\"\n.eqvx A 1\n.macrofoo"));
+		let names: Vec<_> = errors.iter().map(|e| match &e.error {
+			MipsError::UnknownMnemonic(name, line) => { (name.as_str(), *line) }
+			other => { panic!("{:?}", other) }
+		}).collect();
+		assert_eq!(names, vec![(".includefoo", 3), (".eqvx", 4), (".macrofoo", 5)]);
+	}
+
+	#[test]
+	fn test_parser() {
+		let tokens = lexer::tokenize("loop: lw $t1, -4($sp)	# load", 1).unwrap();
+		let kinds: Vec<TokenKind> = tokens.into_iter().map(|t| t.kind).collect();
+		assert_eq!(kinds, vec![
+			TokenKind::Identifier, TokenKind::Colon, TokenKind::Identifier, TokenKind::Register, TokenKind::Comma,
+			TokenKind::Operator("-"), TokenKind::Integer(4), TokenKind::LeftParen, TokenKind::Register, TokenKind::RightParen,
+		]);
+		assert_eq!(lexer::tokenize("'\\n' 'a' 1.5", 1).unwrap()[1].kind, TokenKind::Char(97));
+
+		let line = parser::parse_line("a: b: .asciiz \"x, # y\"	# comment", 1).unwrap();
+		assert_eq!(line.labels, vec!["a", "b"]);
+		let Some(Statement::Directive(".asciiz", operands)) = line.statement else { panic!("expected a directive"); };
+		assert!(matches!(&operands[..], [op] if matches!(&op.kind, OperandKind::String(s) if s == b"x, # y")));
+
+		// commas can be left out between operands that can't be one expression
+		let line = parser::parse_line("add $t0 $t1 $t2", 1).unwrap();
+		let Some(Statement::Instruction("add", operands)) = line.statement else { panic!("expected an instruction"); };
+		assert!(matches!(&operands[..], [a, b, c] if matches!((&a.kind, &b.kind, &c.kind),
+			(OperandKind::Register(8), OperandKind::Register(9), OperandKind::Register(10)))));
+		let line = parser::parse_line("sw $t0 COUNT * 4 ($sp)", 1).unwrap();
+		let Some(Statement::Instruction("sw", operands)) = line.statement else { panic!("expected an instruction"); };
+		assert_eq!(operands[1].text, "COUNT * 4 ($sp)");
+		assert!(matches!(operands[1].kind, OperandKind::Memory(Some(_), 29)));
+		assert_eq!(operands[1].base_register(), Some("$sp"));
+
+		let data = ".text\nmain:\naddi $t0 $zero 'A' + 1\nadd $t1 $t0 $t0";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		assert_eq!(program_words(&intr), vec![0x20080042, 0x01084820]);
+		assert!(matches!(first_error(intr.load_program(".text\nmain:\nadd $t0, $t1 $t2 $t3")), MipsError::SyntaxError(3)));
+	}
+
+	#[test]
+	fn test_strings() {
+		let data =
+			".data\n\
+			msg:\n\
+			.asciiz \"a#b\\t\\\"q\\\"\\n\"	# comment\n\
+			raw:\n\
+			.ascii \"\\x41\\101\\0é\"\n\
+			.text\n\
+			main:\n\
+			addiu $v0, $zero, 1";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		assert_eq!(program_words(&intr), vec![
+			0x61236209,	// a # b \t
+			0x2271220A,	// " q " \n
+			0x00414100,	// NULL, then raw: A A NULL
+			0xC3A90000,	// é as UTF-8, padded out to a whole word
+			0x24020001,
+		]);
+
+		for bad in ["\"open", "\"\\q\"", "\"\\400\"", "\"a\" b"] {
+			let data = format!(".data\n.asciiz {}", bad);
+			let mut intr = mips_int::MipsInterpreter::new();
+			assert!(matches!(first_error(intr.load_program(&data)), MipsError::SyntaxError(2)), "{}", bad);
+		}
+	}
+
+	#[test]
+	fn test_data_lists() {
+		let data =
+			".data\n\
+			bytes:\n\
+			.byte 1, 2, 3\n\
+			table:\n\
+			.word 0:2, main\n\
+			.half -1\n\
+			.byte 7\n\
+			.halfword 0x1234\n\
+			.text\n\
+			main:\n\
+			la $v0, table";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		assert_eq!(program_words(&intr), vec![
+			0x01020300,
+			0x00000000,	// table, moved up to the word boundary
+			0x00000000,
+			0x00400000,	// main
+			0xFFFF0700,
+			0x12340000,	// realigned halfword
+			0x3C011001,	// lui $at, 0x1001
+			0x34220004,	// ori $v0, $at, 4
+		]);
+
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(matches!(first_error(intr.load_program(".data\n.byte 1, 256")), MipsError::ImmediateOutOfRange(256, 2)));
+		assert!(matches!(first_error(intr.load_program(".data\n.word 1,,2")), MipsError::SyntaxError(2)));
+	}
+
+	#[test]
+	fn test_floats() {
+		let data =
+			".data\n\
+			.byte 1\n\
+			f:\n\
+			.float 1.5, -2:3\n\
+			d:\n\
+			.double 0.1\n\
+			.text\n\
+			main:";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		assert_eq!(program_words(&intr), vec![
+			0x01000000,
+			0x3FC00000,	// 1.5
+			0xC0000000,	// -2.0, three times
+			0xC0000000,
+			0xC0000000,
+			0x00000000,	// padding so the double sits on 8 bytes
+			0x3FB99999,	// 0.1
+			0x9999999A,
+		]);
+		assert_eq!(intr.data_size(), 32);
+		assert_eq!(intr.read_f32(intr.label_address("f").unwrap()), Some(1.5));
+		assert_eq!(intr.read_f64(intr.label_address("d").unwrap()), Some(0.1));
+		assert_eq!(intr.read_f64(memory::DATA_BASE + 4), None);
+
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(matches!(first_error(intr.load_program(".data\n.float 1e40")), MipsError::InvalidExpression(_, 2)));
+	}
+
+	#[test]
+	fn test_listing() {
+		let data =
+			".data\n\
+			msg:\n\
+			.asciiz \"hi\"\n\
+			.text\n\
+			main:\n\
+			li $t0, 0x12345678\n\
+			la $a0, msg\n\
+			j main";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let listing = intr.listing();
+		let lines: Vec<&str> = listing.lines().collect();
+		assert!(lines[1].starts_with(".data    0x10010000  68 69 00         3"));
+		assert!(lines[1].ends_with(".asciiz \"hi\""));
+		// both halves of the pseudo instruction point back at it
+		assert!(lines[2].starts_with(".text    0x00400000  0x3c011234       6  lui $at, 4660"));
+		assert!(lines[2].ends_with("li $t0, 0x12345678"));
+		assert!(lines[3].starts_with(".text    0x00400004  0x34285678       6  ori $t0, $at, 22136"));
+		assert!(lines[3].ends_with("li $t0, 0x12345678"));
+		assert!(listing.contains("main                     .text    0x00400000\n"));
+		assert!(listing.contains("msg                      .data    0x10010000\n"));
+		assert!(listing.contains("msg                      2                7\n"));
+		assert!(listing.contains("main                     5                8\n"));
+
+		let path = std::env::temp_dir().join("mips_int_listing.lst");
+		assert!(intr.write_listing(path.to_str().unwrap()).is_ok());
+		assert_eq!(std::fs::read_to_string(&path).unwrap(), listing);
+		assert!(matches!(intr.write_listing("data/no/such/dir.lst"), Err(MipsError::FileNotWritten(_))));
+	}
+
+	#[test]
+	fn test_source_map() {
+		let data =
+			".data\n\
+			msg:\n\
+			.asciiz \"hi\"\n\
+			.text\n\
+			main:\n\
+			\tli $t0, 0x12345678\n\
+			loop:\n\
+			\tj loop";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let at = |addr| intr.source_location(addr).map(|l| (l.line, l.column, l.label.clone()));
+		assert_eq!(at(memory::DATA_BASE + 2), Some((3, 1, Some(String::from("msg")))));
+		assert_eq!(at(memory::DATA_BASE + 3), None);	// past the end of the data
+		// both words of the pseudo instruction map back to it
+		assert_eq!(at(memory::TEXT_BASE), Some((6, 2, Some(String::from("main")))));
+		assert_eq!(at(memory::TEXT_BASE + 4), Some((6, 2, Some(String::from("main")))));
+		assert_eq!(at(memory::TEXT_BASE + 8), Some((8, 2, Some(String::from("loop")))));
+		assert_eq!(at(memory::TEXT_BASE + 12), None);
+		assert_eq!(intr.source_location(memory::TEXT_BASE + 8).unwrap().to_string(), "8:2 in loop: j loop");
+
+		// the text is what was written, before any names were replaced,
+		// and numeric labels don't take over from the label they are under
+		let data =
+			".eqv EXIT 10\n\
+			.text\n\
+			main:\n\
+			1: addi $t0, $t0, 1\n\
+			li $v0, EXIT";
+		assert!(intr.load_program(data).is_ok());
+		let location = intr.source_location(memory::TEXT_BASE).unwrap();
+		assert_eq!((location.text.as_str(), location.label.as_deref()), ("1: addi $t0, $t0, 1", Some("main")));
+		let location = intr.source_location(memory::TEXT_BASE + 4).unwrap();
+		assert_eq!((location.text.as_str(), location.label.as_deref()), ("li $v0, EXIT", Some("main")));
+		let listing = intr.listing();
+		assert!(listing.contains("li $v0, EXIT") && listing.contains("1: addi $t0, $t0, 1"), "{}", listing);
+		assert!(!listing.contains(".L1.0: addi"), "{}", listing);
+
+		// runtime errors say where they happened
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(".data\n.text\nmain:\nlw $t0, 0($zero)").is_ok());
+		assert!(matches!(intr.process_line(), Err(MipsError::AtAddress(memory::TEXT_BASE, 4, _))));
+	}
+
+	#[test]
+	fn test_diagnostics() {
+		let data =
+			".data\n\
+			.text\n\
+			main:\n\
+			\tadd $t10, $t1, $t2\n\
+			\tlui $at, 1\n\
+			\tj nowhere";
+		let mut intr = mips_int::MipsInterpreter::new();
+		let result = intr.load_program(data);
+		let errors = load_errors(result.clone());
+		// every problem is found, not just the first
+		assert_eq!(errors.len(), 3);
+		assert!(matches!(errors[0].error, MipsError::UnknownRegister(ref r, 4) if r == "$t10"));
+		assert_eq!(errors[1].severity, Severity::Warning);
+		assert!(matches!(errors[2].error, MipsError::UndefinedLabel(ref l, 6) if l == "nowhere"));
+
+		let span = errors[0].span.as_ref().unwrap();
+		assert_eq!((span.line, span.column, span.length), (4, 6, 4));
+		assert_eq!(errors[0].render(),
+			"error: unknown register `$t10`\n\
+			\x20--> 4:6\n\
+			\x20 |\n\
+			4 | \tadd $t10, $t1, $t2\n\
+			\x20 | \t    ^^^^\n");
+		assert!(result.unwrap_err().to_string().ends_with("2 errors while assembling"));
+
+		// warnings alone don't stop the load
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(".data\n.text\nmain:\nlui $1, 1").is_ok());
+		assert_eq!(intr.diagnostics().len(), 1);
+		assert!(intr.diagnostics()[0].render().starts_with("warning: `$1` is reserved for the assembler"));
+
+		// nothing in the data segment is dropped without a word
+		let data =
+			".globl main\n\
+			.data\n\
+			\tadd $t0, $t1, $t2\n\
+			\t.wordd 5\n\
+			.text\n\
+			.globl main\n\
+			.extern buffer 4\n\
+			main:";
+		let errors = load_errors(intr.load_program(data));
+		assert_eq!(errors.len(), 2);
+		assert!(matches!(errors[0].error, MipsError::InstructionInData(ref op, 3) if op == "add"));
+		assert!(matches!(errors[1].error, MipsError::UnknownMnemonic(ref op, 4) if op == ".wordd"));
+		let spans: Vec<_> = errors.iter().map(|e| e.span.as_ref().map(|s| (s.line, s.column))).collect();
+		assert_eq!(spans, vec![Some((3, 2)), Some((4, 2))]);
+		assert!(intr.load_program(&data.replace("\tadd $t0, $t1, $t2\n\t.wordd 5\n", "")).is_ok());
+	}
+
+	#[test]
+	fn test_labels() {
+		let data =
+			".data\n\
+			board_data: .word 5\n\
+			msg.2$: .byte 1, 2\n\
+			.text\n\
+			main: addi $t0, $zero, 3\n\
+			loop:\taddi $t0, $t0, -1\n\
+			\tbne $t0, $zero, loop\n\
+			1:\tj 1f\n\
+			\tj 1b\n\
+			1:\tbeq $zero, $zero, 1b";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		assert_eq!(program_words(&intr), vec![
+			0x00000005,
+			0x01020000,
+			0x20080003,	// main: addi $t0, $zero, 3
+			0x2108FFFF,	// loop: addi $t0, $t0, -1
+			0x1500FFFE,	// bne $t0, $zero, loop
+			0x08100005,	// 1: j 1f
+			0x08100003,	// j 1b
+			0x1000FFFF,	// 1: beq $zero, $zero, 1b
+		]);
+		assert_eq!(intr.label_address("board_data"), Some(0x10010000));
+		assert_eq!(intr.label_address("msg.2$"), Some(0x10010004));
+		assert_eq!(intr.label_address("main"), Some(0x00400000));
+
+		let errors = load_errors(intr.load_program(".data\n.text\nmain:\nmain: nop\nmain:"));
+		assert_eq!(errors.len(), 2);
+		assert!(matches!(errors[0].error, MipsError::DuplicateLabel(ref l, 4) if l == "main"));
+		assert!(matches!(first_error(intr.load_program(".data\n.text\nmain: j 2f")), MipsError::UndefinedLabel(ref l, 3) if l == "2f"));
+	}
+
+	#[test]
+	fn test_memory_layout() {
+		let data =
+			".text\n\
+			main:\n\
+			la $a0, value\n\
+			.data\n\
+			.byte 1\n\
+			value: .word 0x11223344\n\
+			.text\n\
+			lw $t0, value";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		// the segments pick up where they left off
+		assert_eq!(intr.label_address("main"), Some(memory::TEXT_BASE));
+		assert_eq!(intr.label_address("value"), Some(memory::DATA_BASE + 4));
+		assert_eq!(intr.memory().text(), &[0x3C011001, 0x34240004, 0x3C011001, 0x8C280004]);
+		assert_eq!(intr.read_word(memory::DATA_BASE + 4), Some(0x11223344));
+		assert_eq!(intr.memory().read_byte(memory::DATA_BASE + 5).unwrap(), 0x22);
+		assert_eq!(intr.memory().read_half(memory::DATA_BASE + 6).unwrap(), 0x3344);
+		assert_eq!(intr.display_register(&RegNames::GP), format!("R28/GP: {}", memory::GLOBAL_POINTER));
+		assert_eq!(intr.display_register(&RegNames::SP), format!("R29/SP: {}", memory::STACK_POINTER));
+
+		let mut mem = Memory::new();
+		mem.push_data(7);
+		assert_eq!(mem.heap_start(), memory::DATA_BASE + 4);
+		assert_eq!(mem.allocate(8).unwrap(), memory::DATA_BASE + 4);
+		assert_eq!(mem.allocate(4).unwrap(), memory::DATA_BASE + 12);
+		assert!(mem.write_word(memory::DATA_BASE + 8, 42).is_ok());
+		assert_eq!(mem.read_word(memory::DATA_BASE + 8).unwrap(), 42);
+		assert!(matches!(mem.read_word(memory::DATA_BASE + 16), Err(MipsError::AddressOutOfRange(_))));
+
+		// the stack reads as zero until it is written
+		assert_eq!(mem.read_word(memory::STACK_POINTER - 8192).unwrap(), 0);
+		assert!(mem.write_word(memory::STACK_POINTER - 8192, 0xDEADBEEF).is_ok());
+		assert!(mem.write_word(memory::STACK_POINTER, 1).is_ok());
+		assert_eq!(mem.read_word(memory::STACK_POINTER - 8192).unwrap(), 0xDEADBEEF);
+		assert_eq!(mem.read_word(memory::STACK_POINTER).unwrap(), 1);
+		assert!(matches!(mem.read_word(memory::STACK_POINTER - 2), Err(MipsError::UnalignedAddress(_))));
+		assert!(matches!(mem.read_byte(0), Err(MipsError::AddressOutOfRange(0))));
+
+		// code can't be written over
+		mem.push_text(0);
+		assert!(matches!(mem.write_word(memory::TEXT_BASE, 1), Err(MipsError::AddressOutOfRange(_))));
+	}
+
+	#[test]
+	fn test_disassembler() {
+		let at = memory::TEXT_BASE;
+		assert_eq!(disassembler::disassemble(0x01095020, at), "add $t2, $t0, $t1");
+		assert_eq!(disassembler::disassemble(0x8FBFFFFC, at), "lw $ra, -4($sp)");
+		assert_eq!(disassembler::disassemble(0x3401FFFF, at), "ori $at, $zero, 65535");
+		assert_eq!(disassembler::disassemble(0x00000000, at), "nop");
+		assert_eq!(disassembler::disassemble(0x0000000C, at), "syscall");
+		assert_eq!(disassembler::disassemble(0x1500FFFF, at), "bne $t0, $zero, 0x00400000");
+		assert_eq!(disassembler::disassemble(0x0C100003, at), "jal 0x0040000c");
+		// encodings the assembler can't produce
+		assert_eq!(disassembler::disassemble(0xFFFFFFFF, at), ".word 0xffffffff");
+		assert_eq!(disassembler::disassemble(0x01095060, at), ".word 0x01095060");	// add with a shift amount
+
+		let mut labels = std::collections::HashMap::new();
+		labels.insert(String::from("loop"), at);
+		labels.insert(String::from("again"), at);
+		assert_eq!(Disassembler::with_labels(&labels).disassemble(0x1500FFFF, at), "bne $t0, $zero, again");
+	}
+
+	#[test]
+	fn test_disassembler_round_trip() {
+		// every opcode and the fields it uses
+		let ops = [
+			(instruction::OP_ADD, 0x03FFF800), (instruction::OP_ADDU, 0x03FFF800),
+			(instruction::OP_SUB, 0x03FFF800), (instruction::OP_SUBU, 0x03FFF800),
+			(instruction::OP_AND, 0x03FFF800), (instruction::OP_OR, 0x03FFF800),
+			(instruction::OP_XOR, 0x03FFF800), (instruction::OP_NOR, 0x03FFF800),
+			(instruction::OP_SLT, 0x03FFF800), (instruction::OP_SLTU, 0x03FFF800),
+			(instruction::OP_SLL, 0x001FFFC0), (instruction::OP_SRL, 0x001FFFC0),
+			(instruction::OP_SRA, 0x001FFFC0), (instruction::OP_SLLV, 0x03FFF800),
+			(instruction::OP_SRLV, 0x03FFF800), (instruction::OP_SRAV, 0x03FFF800),
+			(instruction::OP_MULT, 0x03FF0000), (instruction::OP_MULTU, 0x03FF0000),
+			(instruction::OP_DIV, 0x03FF0000), (instruction::OP_DIVU, 0x03FF0000),
+			(instruction::OP_MFHI, 0x0000F800), (instruction::OP_MFLO, 0x0000F800),
+			(instruction::OP_MTHI, 0x03E00000), (instruction::OP_MTLO, 0x03E00000),
+			(instruction::OP_JR, 0x03E00000), (instruction::OP_SYSCALL, 0),
+			(instruction::OP_ADDI, 0x03FFFFFF), (instruction::OP_ADDIU, 0x03FFFFFF),
+			(instruction::OP_SLTI, 0x03FFFFFF), (instruction::OP_SLTIU, 0x03FFFFFF),
+			(instruction::OP_ANDI, 0x03FFFFFF), (instruction::OP_ORI, 0x03FFFFFF),
+			(instruction::OP_XORI, 0x03FFFFFF), (instruction::OP_LUI, 0x001FFFFF),
+			(instruction::OP_LW, 0x03FFFFFF), (instruction::OP_LH, 0x03FFFFFF),
+			(instruction::OP_LHU, 0x03FFFFFF), (instruction::OP_LB, 0x03FFFFFF),
+			(instruction::OP_LBU, 0x03FFFFFF), (instruction::OP_SW, 0x03FFFFFF),
+			(instruction::OP_SH, 0x03FFFFFF), (instruction::OP_SB, 0x03FFFFFF),
+			(instruction::OP_BEQ, 0x03FFFFFF), (instruction::OP_BNE, 0x03FFFFFF),
+			(instruction::OP_J, 0x03FFFFFF), (instruction::OP_JAL, 0x03FFFFFF),
+		];
+		let mut seed: u32 = 0x2545F491;
+		let mut random = move || {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			seed
+		};
+		for (op, fields) in ops {
+			let words: Vec<u32> = (0..16).map(|_| op | (random() & fields)).collect();
+			let mut source = String::from(".text\nmain:\n");
+			for (i, word) in words.iter().enumerate() {
+				let line = disassembler::disassemble(*word, memory::TEXT_BASE + 4 * i as u32);
+				assert!(!line.starts_with(".word"), "{:#010x}", word);
+				source.push_str(&line);
+				source.push('\n');
+			}
+			let mut intr = mips_int::MipsInterpreter::new();
+			assert!(intr.load_program(&source).is_ok(), "{}", source);
+			assert_eq!(intr.memory().text(), &words[..], "{}", source);
+		}
+
+		// a whole program, with its labels put back where they were
+		let data =
+			".data\n\
+			msg: .asciiz \"hi\"\n\
+			.text\n\
+			main:\n\
+			la $a0, msg\n\
+			li $t0, 10\n\
+			loop: addi $t0, $t0, -1\n\
+			jal print\n\
+			bgt $t0, $zero, loop\n\
+			li $v0, 10\n\
+			syscall\n\
+			print: li $v0, 4\n\
+			syscall\n\
+			jr $ra";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let mut source = String::from(".text\n");
+		for i in 0..intr.memory().text().len() as u32 {
+			let addr = memory::TEXT_BASE + 4 * i;
+			for name in ["main", "loop", "print"] {
+				if intr.label_address(name) == Some(addr) {
+					source.push_str(&format!("{}:\n", name));
+				}
+			}
+			source.push_str(&intr.disassemble(addr).unwrap());
+			source.push('\n');
+		}
+		assert!(source.contains("bne $at, $zero, loop\n"));
+		assert!(source.contains("jal print\n"));
+		let mut again = mips_int::MipsInterpreter::new();
+		assert!(again.load_program(&source).is_ok(), "{}", source);
+		assert_eq!(again.memory().text(), intr.memory().text());
+	}
+
+	#[test]
+	fn test_decoded_instruction() {
+		let r_ops = [
+			instruction::OP_ADD, instruction::OP_ADDU, instruction::OP_SUB, instruction::OP_SUBU,
+			instruction::OP_AND, instruction::OP_OR, instruction::OP_XOR, instruction::OP_NOR,
+			instruction::OP_SLT, instruction::OP_SLTU, instruction::OP_SLL, instruction::OP_SRL,
+			instruction::OP_SRA, instruction::OP_SLLV, instruction::OP_SRLV, instruction::OP_SRAV,
+			instruction::OP_MULT, instruction::OP_MULTU, instruction::OP_DIV, instruction::OP_DIVU,
+			instruction::OP_MFHI, instruction::OP_MFLO, instruction::OP_MTHI, instruction::OP_MTLO,
+			instruction::OP_JR, instruction::OP_SYSCALL,
+		];
+		let i_ops = [
+			instruction::OP_ADDI, instruction::OP_ADDIU, instruction::OP_SLTI, instruction::OP_SLTIU,
+			instruction::OP_ANDI, instruction::OP_ORI, instruction::OP_XORI, instruction::OP_LUI,
+			instruction::OP_LW, instruction::OP_LH, instruction::OP_LHU, instruction::OP_LB,
+			instruction::OP_LBU, instruction::OP_SW, instruction::OP_SH, instruction::OP_SB,
+			instruction::OP_BEQ, instruction::OP_BNE,
+		];
+		let round_trip = |inst: DecodedInstruction, op: u32| {
+			let word = inst.encode();
+			assert_eq!(DecodedInstruction::decode(word), inst, "{:#010x}", word);
+			assert_eq!(DecodedInstruction::decode(word).op(), op, "{:#010x}", word);
+			assert_eq!(OPName::name(op).and_then(OPName::from), Some(op));
+		};
+		// every value of every field, for every opcode
+		for op in r_ops {
+			for v in 0..32 {
+				round_trip(DecodedInstruction::r(op, v, 31 - v, 0, 0), op);
+				round_trip(DecodedInstruction::r(op, 0, v, 31 - v, 31 - v as u32), op);
+				round_trip(DecodedInstruction::r(op, 31 - v, 0, v, v as u32), op);
+			}
+		}
+		for op in i_ops {
+			for v in 0..32 {
+				round_trip(DecodedInstruction::i(op, v, 31 - v, 0x8000), op);
+			}
+			for imm in 0..=u16::MAX {
+				round_trip(DecodedInstruction::i(op, 31, 0, imm), op);
+			}
+		}
+		for op in [instruction::OP_J, instruction::OP_JAL] {
+			for bit in 0..26 {
+				round_trip(DecodedInstruction::j(op, 1 << bit), op);
+				round_trip(DecodedInstruction::j(op, 0x03FFFFFF ^ 1 << bit), op);
+			}
+		}
+		// and any word comes back as itself, whatever it holds
+		for word in (0..=u32::MAX).step_by(65521) {
+			assert_eq!(DecodedInstruction::decode(word).encode(), word, "{:#010x}", word);
+		}
+
+		// sll is all zeros in the opcode and funct, and isn't mistaken for anything else
+		assert_eq!(DecodedInstruction::decode(0), DecodedInstruction::R { rs: 0, rt: 0, rd: 0, shamt: 0, funct: 0 });
+		assert_eq!(DecodedInstruction::decode(0x0C100003), DecodedInstruction::J { opcode: 3, target: 0x100003 });
+		assert_eq!(DecodedInstruction::decode(0x8FBFFFFC), DecodedInstruction::I { opcode: 0x23, rs: 29, rt: 31, imm: 0xFFFC });
+
+		// the assembler puts the operands in the fields the executor reads them from
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(".text\nmain:\nsll $t0, $t1, 3\nsrav $t0, $t1, $t2\nsw $t1, -4($sp)").is_ok());
+		let decoded: Vec<DecodedInstruction> = intr.memory().text().iter().map(|w| DecodedInstruction::decode(*w)).collect();
+		assert_eq!(decoded, vec![
+			DecodedInstruction::R { rs: 0, rt: 9, rd: 8, shamt: 3, funct: instruction::OP_SLL },
+			DecodedInstruction::R { rs: 10, rt: 9, rd: 8, shamt: 0, funct: instruction::OP_SRAV },
+			DecodedInstruction::I { opcode: instruction::OP_SW >> 26, rs: 29, rt: 9, imm: 0xFFFC },
+		]);
+	}
+
+	#[test]
+	fn test_binary_formats() {
+		let data =
+			".data\n\
+			msg: .asciiz \"hi\"\n\
+			.text\n\
+			start: nop\n\
+			main: la $a0, msg\n\
+			j start";
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(data).is_ok());
+		let image = intr.image();
+		assert_eq!(image.entry, memory::TEXT_BASE + 4);
+		assert_eq!(image.segments[1], Segment { address: memory::DATA_BASE, bytes: vec![0x68, 0x69, 0x00] });
+
+		let hex = image.to_intel_hex();
+		let lines: Vec<&str> = hex.lines().collect();
+		assert_eq!(lines[0], ":020000040040BA");
+		assert_eq!(lines[1], ":10000000000000003C011001342400000810000032");
+		assert_eq!(lines[2], ":020000041001E9");
+		assert_eq!(lines[3], ":030000006869002C");
+		assert_eq!(lines[4], ":0400000500400004B3");
+		assert_eq!(lines[5], ":00000001FF");
+		let from_hex = Image::from_intel_hex(&hex).unwrap();
+		assert_eq!(from_hex.segments, image.segments);
+		assert_eq!(from_hex.entry, image.entry);
+		assert!(matches!(Image::from_intel_hex(":00000001FE"), Err(MipsError::InvalidImage(_))));
+		assert!(matches!(Image::from_intel_hex(":0400000500400004B3"), Err(MipsError::InvalidImage(_))));
+		// data up against the top of memory can't be followed by more
+		let record = |kind: u8, address: u16, data: &[u8]| {
+			let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, kind];
+			bytes.extend_from_slice(data);
+			bytes.push(bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg());
+			format!(":{}\n", bytes.iter().map(|b| format!("{:02X}", b)).collect::<String>())
+		};
+		let top = record(4, 0, &[0xFF, 0xFF]) + &record(0, 0xFFF0, &[0; 16]);
+		let end = record(1, 0, &[]);
+		assert_eq!(Image::from_intel_hex(&(top.clone() + &end)).unwrap().segments[0].address, 0xFFFFFFF0);
+		let past = top + &record(0, 0x0000, &[1]) + &end;
+		assert!(matches!(Image::from_intel_hex(&past), Err(MipsError::InvalidImage(_))));
+
+		// every format loads back into the same memory
+		let dir = std::env::temp_dir();
+		let file = |name: &str| String::from(dir.join(name).to_str().unwrap());
+		assert!(intr.write_binary(&file("mips_int.text.bin"), &file("mips_int.data.bin")).is_ok());
+		assert!(intr.write_hex(&file("mips_int.hex")).is_ok());
+		assert!(intr.write_elf(&file("mips_int.elf")).is_ok());
+		let mut loaded = mips_int::MipsInterpreter::new();
+		assert!(loaded.load_binary_files(&file("mips_int.text.bin"), Some(&file("mips_int.data.bin"))).is_ok());
+		assert_eq!(loaded.image().segments, image.segments);
+		assert_eq!(loaded.image().entry, memory::TEXT_BASE);
+		assert!(loaded.load_hex_file(&file("mips_int.hex")).is_ok());
+		assert_eq!(loaded.image().segments, image.segments);
+		assert!(loaded.load_elf_file(&file("mips_int.elf")).is_ok());
+		assert_eq!(loaded.image().segments, image.segments);
+		assert_eq!(loaded.image().entry, image.entry);
+		assert_eq!(loaded.image().symbols, image.symbols);
+		assert_eq!(loaded.disassemble(memory::TEXT_BASE + 12), Some(String::from("j start")));
+		assert_eq!(loaded.read_word(memory::DATA_BASE), Some(0x68690000));
+
+		assert!(matches!(loaded.load_elf_file(&file("mips_int.hex")), Err(MipsError::InvalidImage(_))));
+	}
+
+	#[test]
+	fn test_elf_loader() {
+		// the same program linked big and little endian, see data/tests/elf/make_fixtures.py
+		for (name, hell) in [("hello_be.elf", 0x68656c6c), ("hello_le.elf", 0x6c6c6568)] {
+			let mut intr = mips_int::MipsInterpreter::new();
+			assert!(intr.load_elf_file(&format!("data/tests/elf/{}", name)).is_ok(), "{}", name);
+			assert_eq!(intr.image().entry, 0x00400100);
+			assert_eq!(intr.display_register(&RegNames::PC), format!("PC: {}", 0x00400100));
+			assert_eq!(intr.display_register(&RegNames::GP), format!("R28/GP: {}", 0x00418200));
+			assert_eq!(intr.label_address("_start"), Some(0x00400100));
+			assert_eq!(intr.label_address("msg"), Some(0x00410200));
+			assert_eq!(intr.label_address("counter"), Some(0x00410208));
+			assert_eq!(intr.label_address("missing"), None);
+			assert_eq!(intr.label_address("start.s"), None);
+			assert_eq!(intr.disassemble(0x00400100), Some(String::from("lui $a0, 65")));
+			assert_eq!(intr.disassemble(0x00400104), Some(String::from("ori $a0, $a0, 512")));
+			// the same bytes make a different word in each byte order
+			assert_eq!(intr.read_word(0x00410200), Some(hell));
+			assert_eq!(intr.memory().read_byte(0x00410200).ok(), Some(b'h'));
+			// .bss is past the end of the file
+			assert_eq!(intr.read_word(0x0041020C), Some(0));
+		}
+
+		let mut intr = mips_int::MipsInterpreter::new();
+		for name in ["relocations.elf", "reloc_mips.o", "x86.o"] {
+			let result = intr.load_elf_file(&format!("data/tests/elf/{}", name));
+			assert!(matches!(result, Err(MipsError::UnsupportedElf(_))), "{}", name);
+		}
+		let Err(error) = intr.load_elf_file("data/tests/elf/relocations.elf") else { panic!() };
+		assert!(error.message().contains("`.rel.text`"));
+		let Err(error) = intr.load_elf_file("data/tests/elf/x86.o") else { panic!() };
+		assert!(error.message().contains("x86"));
+
+		// little endian programs are written back out little endian
+		assert!(intr.load_elf_file("data/tests/elf/hello_le.elf").is_ok());
+		let image = elf::read(&elf::write(&intr.image())).unwrap();
+		assert_eq!(image.endian, memory::Endian::Little);
+		assert_eq!(image.segments, intr.image().segments);
+		assert_eq!(image.entry, 0x00400100);
+
+		// offsets and sizes that point anywhere are errors, not panics
+		let good = std::fs::read("data/tests/elf/hello_be.elf").unwrap();
+		let phoff = u32::from_be_bytes([good[28], good[29], good[30], good[31]]) as usize;
+		let shoff = u32::from_be_bytes([good[32], good[33], good[34], good[35]]) as usize;
+		let patched = |at: usize, value: u32| {
+			let mut bytes = good.clone();
+			bytes[at..at + 4].copy_from_slice(&value.to_be_bytes());
+			elf::read(&bytes)
+		};
+		for (at, value) in [
+			(28, 0xFFFFFFF0),			// program headers past the end of the address space
+			(32, 0xFFFFFFF0),			// and the section headers
+			(phoff + 4, 0xFFFFFFFF),	// a segment's bytes
+			(phoff + 16, 0xFFFFFFFF),	// more bytes than the file has
+			(phoff + 20, 0xFFFFFFFF),	// too much .bss
+			(phoff + 8, 0xFFFFFF00),	// a segment wrapping around the top of memory
+		] {
+			assert!(matches!(patched(at, value), Err(MipsError::InvalidImage(_))), "{:#x}", at);
+		}
+		// every section's name and the symbol names
+		for section in 0..u16::from_be_bytes([good[48], good[49]]) as usize {
+			let result = patched(shoff + 40 * section, 0xFFFFFFFF);
+			assert!(result.is_ok() || matches!(result, Err(MipsError::InvalidImage(_))));
+			for field in [16, 24] {
+				let result = patched(shoff + 40 * section + field, 0xFFFFFFFF);
+				assert!(result.is_ok() || matches!(result, Err(MipsError::InvalidImage(_))));
+			}
+		}
+	}
+
+	#[test]
+	fn test_assembler() {
+		let data =
+			".data\n\
+			msg: .asciiz \"hi\"\n\
+			.text\n\
+			main:\n\
+			\tla $a0, msg\n\
+			\tlui $at, 1\n\
+			\tj main";
+		// no interpreter needed to assemble
+		let program = Assembler::new().assemble(data).unwrap();
+		assert_eq!(program.image.entry, memory::TEXT_BASE);
+		assert_eq!(program.image.symbols.get("msg"), Some(&memory::DATA_BASE));
+		assert_eq!(program.image.segments[0].address, memory::TEXT_BASE);
+		assert_eq!(program.image.segments[0].bytes.len(), 16);
+		assert_eq!(program.image.segments[1], Segment { address: memory::DATA_BASE, bytes: vec![0x68, 0x69, 0x00] });
+		assert_eq!(program.source_map.lookup(memory::TEXT_BASE + 12).map(|l| l.line), Some(7));
+		assert_eq!(program.diagnostics.len(), 1);
+		assert_eq!(program.diagnostics[0].severity, Severity::Warning);
+		assert!(program.listing().contains("lui $at, 4097"));
+
+		// assembled once, loaded as many times as needed
+		let mut first = mips_int::MipsInterpreter::new();
+		let mut second = mips_int::MipsInterpreter::new();
+		for intr in [&mut first, &mut second] {
+			assert!(intr.load(&program).is_ok());
+			assert_eq!(intr.display_register(&RegNames::PC), format!("PC: {}", memory::TEXT_BASE));
+			assert_eq!(intr.label_address("msg"), Some(memory::DATA_BASE));
+			assert_eq!(intr.disassemble(memory::TEXT_BASE + 12), Some(String::from("j main")));
+			assert_eq!(intr.source_location(memory::TEXT_BASE).map(|l| l.line), Some(5));
+			assert_eq!(intr.diagnostics().len(), 1);
+			assert_eq!(intr.listing(), program.listing());
+		}
+		assert!(first.load(&program).is_ok());
+		assert_eq!(program_words(&first), program_words(&second));
+
+		// a program with errors doesn't get made, and nothing is loaded
+		let result = Assembler::new().assemble(".text\nmain: j nowhere");
+		assert!(matches!(result, Err(MipsError::Diagnostics(ref d)) if d.len() == 1));
+		assert!(first.load_program(".text\nmain: j nowhere").is_err());
+		assert_eq!(first.diagnostics().len(), 1);
+		assert_eq!(first.label_address("main"), None);
+		assert_eq!(first.read_word(memory::TEXT_BASE), None);
+	}
+
+	#[test]
+	fn test_exec_add() {
+		let intr = execute("li $t0, 5\nli $t1, -7\nadd $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), -2i32 as u32);
+	}
+
+	#[test]
+	fn test_exec_addu() {
+		let intr = execute("li $t0, -1\nli $t1, 2\naddu $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 1);
+	}
+
+	#[test]
+	fn test_exec_addi() {
+		let intr = execute("li $t0, 10\naddi $t1, $t0, -20");
+		assert_eq!(intr.get_register(&RegNames::T1), -10i32 as u32);
+	}
+
+	#[test]
+	fn test_exec_addiu() {
+		let intr = execute("addiu $t1, $zero, -1\naddiu $zero, $zero, 5");
+		assert_eq!(intr.get_register(&RegNames::T1), 0xFFFFFFFF);
+		// writes to $zero are thrown away
+		assert_eq!(intr.get_register(&RegNames::ZERO), 0);
+	}
+
+	#[test]
+	fn test_exec_sub() {
+		let intr = execute("li $t0, 5\nli $t1, 7\nsub $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), -2i32 as u32);
+	}
+
+	#[test]
+	fn test_exec_subu() {
+		let intr = execute("li $t1, 1\nsubu $t2, $zero, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 0xFFFFFFFF);
+	}
+
+	#[test]
+	fn test_exec_overflow() {
+		// the unsigned forms wrap
+		let intr = execute("li $t0, 0x7fffffff\naddu $t1, $t0, $t0\naddiu $t2, $t0, 1\n\
+			li $t3, 0x80000000\naddiu $t4, $t3, -1\nsubu $t5, $zero, $t3");
+		assert_eq!(intr.get_register(&RegNames::T1), 0xFFFFFFFE);
+		assert_eq!(intr.get_register(&RegNames::T2), 0x80000000);
+		assert_eq!(intr.get_register(&RegNames::T4), 0x7FFFFFFF);
+		assert_eq!(intr.get_register(&RegNames::T5), 0x80000000);
+
+		// the signed ones trap, leaving the destination alone and the PC on the instruction
+		for code in [
+			"li $t0, 0x7fffffff\nli $t1, 1\nadd $t2, $t0, $t1",
+			"li $t0, 0x80000000\nli $t1, -1\nadd $t2, $t0, $t1",
+			"li $t0, 0x80000000\nli $t1, 1\nsub $t2, $t0, $t1",
+			"li $t0, 0x7fffffff\nli $t1, -1\nsub $t2, $t0, $t1",
+			"li $t0, 0x7fffffff\naddi $t2, $t0, 1",
+			"li $t0, 0x80000000\naddi $t2, $t0, -1",
+		] {
+			let mut intr = mips_int::MipsInterpreter::new();
+			assert!(intr.load_program(&format!(".text\nmain:\n{}", code)).is_ok(), "{}", code);
+			let last = memory::TEXT_BASE + 4 * (intr.memory().text().len() as u32 - 1);
+			while intr.get_register(&RegNames::PC) < last {
+				intr.process_line().unwrap();
+			}
+			let result = intr.process_line();
+			assert!(matches!(result, Err(MipsError::AtAddress(_, _, ref e)) if matches!(**e, MipsError::ArithmeticOverflow)), "{}", code);
+			assert_eq!(intr.get_register(&RegNames::T2), 0, "{}", code);
+			assert_eq!(intr.get_register(&RegNames::PC), last, "{}", code);
+		}
+		// the edges that just fit don't
+		let intr = execute("li $t0, 0x7fffffff\nli $t1, 0x80000000\nadd $t2, $t0, $t1\nsub $t3, $t1, $t1\naddi $t4, $t0, -32768");
+		assert_eq!(intr.get_register(&RegNames::T2), 0xFFFFFFFF);
+		assert_eq!(intr.get_register(&RegNames::T3), 0);
+		assert_eq!(intr.get_register(&RegNames::T4), 0x7FFF7FFF);
+
+		// the PC wraps around the top of memory rather than panicking
+		let mut pc = register::Register::new(RegNames::PC);
+		pc.set_u32(0xFFFFFFFC);
+		pc.add_u32(8);
+		assert_eq!(pc.get_u32(), 4);
+	}
+
+	#[test]
+	fn test_exec_and() {
+		let intr = execute("li $t0, 0x0F0F\nli $t1, 0x00FF\nand $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 0x000F);
+	}
+
+	#[test]
+	fn test_exec_andi() {
+		// the immediate is zero extended
+		let intr = execute("li $t0, -1\nandi $t1, $t0, 0x8001");
+		assert_eq!(intr.get_register(&RegNames::T1), 0x8001);
+	}
+
+	#[test]
+	fn test_exec_or() {
+		let intr = execute("li $t0, 0x0F0F\nli $t1, 0x00FF\nor $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 0x0FFF);
+	}
+
+	#[test]
+	fn test_exec_ori() {
+		let intr = execute("lui $t0, 1\nori $t1, $t0, 0xFFFF");
+		assert_eq!(intr.get_register(&RegNames::T1), 0x1FFFF);
+	}
+
+	#[test]
+	fn test_exec_xor() {
+		let intr = execute("li $t0, 0x0F0F\nli $t1, 0x00FF\nxor $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 0x0FF0);
+	}
+
+	#[test]
+	fn test_exec_xori() {
+		let intr = execute("li $t0, -1\nxori $t1, $t0, 0xF0F0");
+		assert_eq!(intr.get_register(&RegNames::T1), 0xFFFF0F0F);
+	}
+
+	#[test]
+	fn test_exec_nor() {
+		let intr = execute("li $t0, 0x0F\nli $t1, 0xF0\nnor $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 0xFFFFFF00);
+	}
+
+	#[test]
+	fn test_exec_sll() {
+		let intr = execute("li $t0, 3\nsll $t1, $t0, 4\nsll $t2, $t0, 31");
+		assert_eq!(intr.get_register(&RegNames::T1), 48);
+		assert_eq!(intr.get_register(&RegNames::T2), 0x80000000);
+	}
+
+	#[test]
+	fn test_exec_srl() {
+		let intr = execute("li $t0, -16\nsrl $t1, $t0, 2");
+		assert_eq!(intr.get_register(&RegNames::T1), 0x3FFFFFFC);
+	}
+
+	#[test]
+	fn test_exec_sra() {
+		let intr = execute("li $t0, -16\nsra $t1, $t0, 2");
+		assert_eq!(intr.get_register(&RegNames::T1), -4i32 as u32);
+	}
+
+	#[test]
+	fn test_exec_sllv() {
+		// only the low 5 bits of the amount count
+		let intr = execute("li $t0, 1\nli $t1, 35\nsllv $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 8);
+	}
+
+	#[test]
+	fn test_exec_srlv() {
+		let intr = execute("li $t0, -1\nli $t1, 28\nsrlv $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), 0xF);
+	}
+
+	#[test]
+	fn test_exec_srav() {
+		let intr = execute("li $t0, -256\nli $t1, 4\nsrav $t2, $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::T2), -16i32 as u32);
+	}
+
+	#[test]
+	fn test_exec_slt() {
+		let intr = execute("li $t0, -1\nli $t1, 1\nslt $t2, $t0, $t1\nslt $t3, $t1, $t0");
+		assert_eq!(intr.get_register(&RegNames::T2), 1);
+		assert_eq!(intr.get_register(&RegNames::T3), 0);
+	}
+
+	#[test]
+	fn test_exec_sltu() {
+		let intr = execute("li $t0, -1\nli $t1, 1\nsltu $t2, $t0, $t1\nsltu $t3, $t1, $t0");
+		assert_eq!(intr.get_register(&RegNames::T2), 0);
+		assert_eq!(intr.get_register(&RegNames::T3), 1);
+	}
+
+	#[test]
+	fn test_exec_slti() {
+		let intr = execute("li $t0, -5\nslti $t1, $t0, -4\nslti $t2, $t0, -5");
+		assert_eq!(intr.get_register(&RegNames::T1), 1);
+		assert_eq!(intr.get_register(&RegNames::T2), 0);
+	}
+
+	#[test]
+	fn test_exec_sltiu() {
+		// -1 is sign extended, then compared as the largest unsigned number
+		let intr = execute("li $t0, 5\nsltiu $t1, $t0, -1\nsltiu $t2, $t0, 5");
+		assert_eq!(intr.get_register(&RegNames::T1), 1);
+		assert_eq!(intr.get_register(&RegNames::T2), 0);
+	}
+
+	#[test]
+	fn test_exec_mult() {
+		let intr = execute("li $t0, -3\nli $t1, 0x40000000\nmult $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::HI), 0xFFFFFFFF);
+		assert_eq!(intr.get_register(&RegNames::LO), 0x40000000);
+		let intr = execute("li $t0, 0x80000000\nmult $t0, $t0");
+		assert_eq!(intr.get_register(&RegNames::HI), 0x40000000);
+		assert_eq!(intr.get_register(&RegNames::LO), 0);
+		let intr = execute("li $t0, 0x80000000\nli $t1, 0x7fffffff\nmult $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::HI), 0xC0000000);
+		assert_eq!(intr.get_register(&RegNames::LO), 0x80000000);
+	}
+
+	#[test]
+	fn test_exec_multu() {
+		let intr = execute("li $t0, -1\nli $t1, 2\nmultu $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::HI), 1);
+		assert_eq!(intr.get_register(&RegNames::LO), 0xFFFFFFFE);
+		let intr = execute("li $t0, -1\nmultu $t0, $t0");
+		assert_eq!(intr.get_register(&RegNames::HI), 0xFFFFFFFE);
+		assert_eq!(intr.get_register(&RegNames::LO), 1);
+	}
+
+	#[test]
+	fn test_exec_div() {
+		let intr = execute("li $t0, -7\nli $t1, 2\ndiv $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::LO), -3i32 as u32);
+		assert_eq!(intr.get_register(&RegNames::HI), -1i32 as u32);
+		let intr = execute("li $t0, 0x80000000\nli $t1, -1\ndiv $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::LO), i32::MIN as u32);
+		assert_eq!(intr.get_register(&RegNames::HI), 0);
+		// dividing by zero carries on without touching HI and LO
+		let intr = execute("li $t0, 5\nmthi $t0\nmtlo $t0\ndiv $t0, $zero\ndivu $t0, $zero\nli $t1, 1");
+		assert_eq!(intr.get_register(&RegNames::HI), 5);
+		assert_eq!(intr.get_register(&RegNames::LO), 5);
+		assert_eq!(intr.get_register(&RegNames::T1), 1);
+	}
+
+	#[test]
+	fn test_exec_divu() {
+		let intr = execute("li $t0, -7\nli $t1, 2\ndivu $t0, $t1");
+		assert_eq!(intr.get_register(&RegNames::LO), 0x7FFFFFFC);
+		assert_eq!(intr.get_register(&RegNames::HI), 1);
+	}
+
+	#[test]
+	fn test_exec_mfhi() {
+		let intr = execute("li $t0, 7\nli $t1, 2\ndiv $t0, $t1\nmfhi $t2");
+		assert_eq!(intr.get_register(&RegNames::T2), 1);
+	}
+
+	#[test]
+	fn test_exec_mflo() {
+		let intr = execute("li $t0, 7\nli $t1, 2\ndiv $t0, $t1\nmflo $t2");
+		assert_eq!(intr.get_register(&RegNames::T2), 3);
+	}
+
+	#[test]
+	fn test_exec_mthi() {
+		let intr = execute("li $t0, 42\nmthi $t0");
+		assert_eq!(intr.get_register(&RegNames::HI), 42);
+	}
+
+	#[test]
+	fn test_exec_mtlo() {
+		let intr = execute("li $t0, 42\nmtlo $t0");
+		assert_eq!(intr.get_register(&RegNames::LO), 42);
+	}
+
+	#[test]
+	fn test_exec_lui() {
+		let intr = execute("lui $t0, 0x8001");
+		assert_eq!(intr.get_register(&RegNames::T0), 0x80010000);
+	}
+
+	#[test]
+	fn test_exec_lw() {
+		let intr = execute("la $t0, value\nlw $t1, 4($t0)\nlw $t2, value");
+		assert_eq!(intr.get_register(&RegNames::T1), 7);
+		assert_eq!(intr.get_register(&RegNames::T2), 0x8081FFFE);
+	}
+
+	#[test]
+	fn test_exec_lh() {
+		let intr = execute("la $t0, value\nlh $t1, 0($t0)\nlh $t2, 6($t0)");
+		assert_eq!(intr.get_register(&RegNames::T1), 0xFFFF8081);
+		assert_eq!(intr.get_register(&RegNames::T2), 7);
+	}
+
+	#[test]
+	fn test_exec_lhu() {
+		let intr = execute("la $t0, value\nlhu $t1, 0($t0)");
+		assert_eq!(intr.get_register(&RegNames::T1), 0x8081);
+	}
+
+	#[test]
+	fn test_exec_lb() {
+		let intr = execute("la $t0, value\nlb $t1, 1($t0)\nlb $t2, 3($t0)");
+		assert_eq!(intr.get_register(&RegNames::T1), 0xFFFFFF81);
+		assert_eq!(intr.get_register(&RegNames::T2), -2i32 as u32);
+	}
+
+	#[test]
+	fn test_exec_lbu() {
+		let intr = execute("la $t0, value\nlbu $t1, 1($t0)");
+		assert_eq!(intr.get_register(&RegNames::T1), 0x81);
+	}
+
+	#[test]
+	fn test_exec_sw() {
+		let intr = execute("la $t0, value\nli $t1, 0x12345678\nsw $t1, 4($t0)\naddi $sp, $sp, -4\nsw $t1, 0($sp)");
+		assert_eq!(intr.read_word(memory::DATA_BASE + 4), Some(0x12345678));
+		assert_eq!(intr.read_word(memory::STACK_POINTER - 4), Some(0x12345678));
+	}
+
+	#[test]
+	fn test_exec_sh() {
+		let intr = execute("la $t0, value\nli $t1, 0x12345678\nsh $t1, 2($t0)");
+		assert_eq!(intr.read_word(memory::DATA_BASE), Some(0x80815678));
+	}
+
+	#[test]
+	fn test_exec_sb() {
+		let intr = execute("la $t0, value\nli $t1, 0x12345678\nsb $t1, 3($t0)");
+		assert_eq!(intr.read_word(memory::DATA_BASE), Some(0x8081FF78));
+	}
+
+	#[test]
+	fn test_exec_beq() {
+		let intr = execute("li $t0, 1\nbeq $t0, $zero, first\nli $t1, 1\nfirst:\nbeq $t0, $t0, second\nli $t2, 1\nsecond:");
+		assert_eq!(intr.get_register(&RegNames::T1), 1);	// not taken
+		assert_eq!(intr.get_register(&RegNames::T2), 0);	// taken
+	}
+
+	#[test]
+	fn test_exec_bne() {
+		let intr = execute("li $t0, 3\nloop:\naddi $t1, $t1, 1\naddi $t0, $t0, -1\nbne $t0, $zero, loop");
+		assert_eq!(intr.get_register(&RegNames::T1), 3);	// taken backwards twice
+	}
+
+	#[test]
+	fn test_exec_j() {
+		let intr = execute("j skip\nli $t1, 1\nskip:\nli $t2, 2");
+		assert_eq!(intr.get_register(&RegNames::T1), 0);
+		assert_eq!(intr.get_register(&RegNames::T2), 2);
+	}
+
+	#[test]
+	fn test_exec_jal() {
+		let intr = execute("jal func\nnop\nfunc:\nli $t2, 2");
+		assert_eq!(intr.get_register(&RegNames::RA), memory::TEXT_BASE + 4);
+		assert_eq!(intr.get_register(&RegNames::T2), 2);
+	}
+
+	#[test]
+	fn test_exec_jr() {
+		let intr = execute("jal func\nj done\nfunc:\nli $t1, 1\njr $ra\ndone:\nli $t2, 2");
+		assert_eq!(intr.get_register(&RegNames::T1), 1);
+		assert_eq!(intr.get_register(&RegNames::T2), 2);
+	}
+
+	#[test]
+	fn test_exec_fetch() {
+		// running off the end of the text is an error, not a panic
+		let mut intr = execute("li $t0, 1");
+		let end = memory::TEXT_BASE + 4;
+		assert!(matches!(intr.process_line(), Err(MipsError::PcOutOfText(pc)) if pc == end));
+		assert_eq!(intr.get_register(&RegNames::PC), end);
+
+		// and so is jumping into the data or between instructions
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(".data\nvalue: .word 0\n.text\nmain:\nla $t0, value\njr $t0").is_ok());
+		for _ in 0..3 {
+			intr.process_line().unwrap();
+		}
+		assert!(matches!(intr.process_line(), Err(MipsError::PcOutOfText(memory::DATA_BASE))));
+		assert!(intr.load_program(".text\nmain:\nli $t0, 0x00400002\njr $t0").is_ok());
+		for _ in 0..3 {
+			intr.process_line().unwrap();
+		}
+		assert!(matches!(intr.process_line(), Err(MipsError::UnalignedPc(0x00400002))));
+
+		// code loaded from a file runs from wherever it was placed
+		assert!(intr.load_elf_file("data/tests/elf/hello_le.elf").is_ok());
+		intr.process_line().unwrap();
+		assert_eq!(intr.get_register(&RegNames::A0), 0x00410000);
+	}
+
+	#[test]
+	fn test_exec_syscall() {
+		let intr = execute("li $v0, 1\nli $a0, -42\nsyscall\nli $v0, 11\nli $a0, ' '\nsyscall\n\
+			li $v0, 4\nla $a0, msg\nsyscall\nli $v0, 17\nli $a0, 3\nsyscall\nli $t0, 1");
+		assert_eq!(intr.output(), "-42 hi");
+		assert_eq!(intr.exit_code(), Some(3));
+		assert_eq!(intr.get_register(&RegNames::T0), 0);	// nothing runs after the exit
+
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program(".text\nmain:\nli $v0, 5\nsyscall\nmove $t0, $v0").is_ok());
+		for _ in 0..3 {
+			intr.process_line().unwrap();
+		}
+		// the read waits on the syscall until a whole line is typed
+		assert!(intr.waiting_for_input());
+		assert_eq!(intr.get_register(&RegNames::PC), memory::TEXT_BASE + 4);
+		intr.provide_input("-12\n");
+		intr.process_line().unwrap();
+		intr.process_line().unwrap();
+		assert!(!intr.waiting_for_input());
+		assert_eq!(intr.get_register(&RegNames::T0), -12i32 as u32);
+
+		assert!(intr.load_program(".text\nmain:\nli $v0, 99\nsyscall").is_ok());
+		intr.process_line().unwrap();
+		assert!(matches!(intr.process_line(), Err(MipsError::AtAddress(_, 4, ref e)) if matches!(**e, MipsError::UnknownSyscall(99))));
+	}
+
+	#[test]
+	fn test_run() {
+		let mut intr = mips_int::MipsInterpreter::new();
+		assert!(intr.load_program_file("data/game.asm").is_ok());
+		assert!(matches!(intr.run(1_000_000), StopReason::WaitingForInput));
+		let waited_at = intr.instruction_count();
+		assert!(waited_at > 0);
+		// nothing more runs until there is something to read
+		assert!(matches!(intr.run(1_000_000), StopReason::WaitingForInput));
+		assert_eq!(intr.instruction_count(), waited_at);
+		intr.provide_input("-1\n-1\n");
+		assert!(matches!(intr.run(1_000_000), StopReason::Exited(0)));
+		assert!(intr.output().contains("pegs"));
+		let count = intr.instruction_count();
+		assert!(matches!(intr.run(10), StopReason::Exited(0)));
+		assert_eq!(intr.instruction_count(), count);
+
+		// an endless loop stops at the limit, and carries on from there
+		assert!(intr.load_program(".text\nmain:\naddi $t0, $t0, 1\nj main").is_ok());
+		assert_eq!(intr.instruction_count(), 0);
+		assert!(matches!(intr.run(1001), StopReason::StepLimit));
+		assert!(matches!(intr.run(1000), StopReason::StepLimit));
+		assert_eq!(intr.instruction_count(), 2001);
+		assert_eq!(intr.get_register(&RegNames::T0), 1001);
+		assert!(matches!(intr.run(0), StopReason::StepLimit));
+
+		// stopping before a breakpoint or wherever a condition holds
+		let source = ".text\nmain:\nli $t0, 3\nloop:\naddi $t0, $t0, -1\nbne $t0, $zero, loop\n\
+			li $v0, 17\nli $a0, 4\nsyscall\nli $v0, 99\nsyscall";
+		assert!(intr.load_program(source).is_ok());
+		let exit = intr.label_address("loop").unwrap() + 8;
+		intr.add_breakpoint(exit);
+		assert!(matches!(intr.run(100), StopReason::Breakpoint(pc) if pc == exit));
+		assert_eq!(intr.get_register(&RegNames::T0), 0);
+		assert_eq!(intr.instruction_count(), 7);
+		assert!(matches!(intr.run(100), StopReason::Exited(4)));
+		// loading again starts over with the breakpoint still set
+		assert!(intr.load_program(source).is_ok());
+		assert!(matches!(intr.run_until(|intr| intr.get_register(&RegNames::T0) == 1), StopReason::Breakpoint(pc) if pc == exit - 4));
+		assert!(matches!(intr.run_until(|_| false), StopReason::Breakpoint(pc) if pc == exit));
+		assert!(intr.remove_breakpoint(exit));
+		assert!(!intr.remove_breakpoint(exit));
+
+		// failing stops on the instruction that failed
+		assert!(intr.load_program(&source.replace("17", "99")).is_ok());
+		assert!(matches!(intr.run(100), StopReason::Exception(MipsError::AtAddress(_, 9, _))));
+		assert_eq!(intr.get_register(&RegNames::PC), exit + 8);
+	}
 }